@@ -0,0 +1,191 @@
+use crate::{
+    collision::Collider,
+    physics::{DeltaTime, Position, Velocity, WorldBounds},
+    projectile::LaserGun,
+    random::WorldRng,
+    user::Player,
+};
+use rand::Rng;
+use specs::{prelude::*, Component, DenseVecStorage};
+use std::f64::consts::PI;
+
+type Vec2 = vek::Vec2<f64>;
+
+#[const_tweaker::tweak(min = 0.0, max = 200.0, step = 1.0)]
+const ENEMY_SPEED: f64 = 40.0;
+/// How fast the enemies can change their velocity, higher is more agile.
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const STEERING: f64 = 1.5;
+/// Enemies further away from the player than this just wander around.
+#[const_tweaker::tweak(min = 0.0, max = 2000.0, step = 10.0)]
+const SIGHT_RANGE: f64 = 500.0;
+/// The distance orbiting enemies keep from the player.
+#[const_tweaker::tweak(min = 0.0, max = 1000.0, step = 10.0)]
+const ORBIT_RADIUS: f64 = 200.0;
+/// How fast the wander direction changes, in radians per second.
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const WANDER_JITTER: f64 = 2.0;
+/// How far the enemies can miss when shooting at the player.
+#[const_tweaker::tweak(min = 0.0, max = 200.0, step = 1.0)]
+const AIM_ERROR: f64 = 30.0;
+#[const_tweaker::tweak(min = 0.0, max = 2000.0, step = 1.0)]
+const ENEMY_LASER_STRENGTH: f64 = 300.0;
+#[const_tweaker::tweak(min = 0.0, max = 5.0, step = 0.01)]
+const ENEMY_FIRE_DELAY: f64 = 1.5;
+
+/// What an enemy does when it sees the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Behaviour {
+    /// Fly around aimlessly.
+    Wander,
+    /// Fly straight at the player.
+    Seek,
+    /// Fly away from the player.
+    Flee,
+    /// Circle around the player.
+    Orbit,
+    /// Circle around the player while shooting at it.
+    StrafeAndShoot,
+}
+
+impl Behaviour {
+    /// All behaviours.
+    pub const ALL: [Behaviour; 5] = [
+        Behaviour::Wander,
+        Behaviour::Seek,
+        Behaviour::Flee,
+        Behaviour::Orbit,
+        Behaviour::StrafeAndShoot,
+    ];
+}
+
+/// Steers an enemy rocket, enemies that are too far away from the player wander around.
+#[derive(Component, Debug)]
+pub struct AiController {
+    pub behaviour: Behaviour,
+    /// The direction the enemy wanders in, in radians.
+    wander_angle: f64,
+}
+
+impl AiController {
+    /// Instantiate a new controller wandering in the direction, in radians.
+    pub fn new(behaviour: Behaviour, wander_angle: f64) -> Self {
+        Self {
+            behaviour,
+            wander_angle,
+        }
+    }
+
+    /// Instantiate a new controller with a random behaviour.
+    pub fn random<R: Rng>(rng: &mut R) -> Self {
+        Self::new(
+            Behaviour::ALL[rng.gen_range(0, Behaviour::ALL.len())],
+            rng.gen_range(-PI, PI),
+        )
+    }
+}
+
+/// A gun for an enemy rocket, it's weaker and slower than the one of the player.
+///
+/// The cooldown starts at a random point so the enemies don't all shoot at the same time.
+pub fn enemy_laser_gun<R: Rng>(nose: f64, rng: &mut R) -> LaserGun {
+    LaserGun {
+        strength: *ENEMY_LASER_STRENGTH,
+        fire_delay: *ENEMY_FIRE_DELAY,
+        cooldown: rng.gen_range(0.0, *ENEMY_FIRE_DELAY),
+        autofire: true,
+        ..LaserGun::new(nose)
+    }
+}
+
+pub struct AiSystem;
+impl<'a> System<'a> for AiSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, WorldBounds>,
+        Write<'a, WorldRng>,
+        WriteStorage<'a, AiController>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, LaserGun>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Collider>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, dt, bounds, mut rng, mut ai, mut vel, mut gun, player, pos, collider): Self::SystemData,
+    ) {
+        let dt = dt.to_seconds();
+
+        let center = |entity: Entity, pos: &Position| match collider.get(entity) {
+            Some(collider) => pos.0 + collider.offset,
+            None => pos.0,
+        };
+
+        let player_center = (&entities, &player, &pos)
+            .join()
+            .map(|(entity, _, pos)| center(entity, pos))
+            .next();
+
+        for (entity, ai, vel, pos) in (&entities, &mut ai, &mut vel, &pos).join() {
+            let own_center = center(entity, pos);
+
+            // Look at the nearest copy of the player, it can be across the world seam
+            let target = player_center
+                .map(|player_center| bounds.nearest(player_center, own_center))
+                .filter(|target| target.distance(own_center) < *SIGHT_RANGE);
+            let behaviour = if target.is_some() {
+                ai.behaviour
+            } else {
+                Behaviour::Wander
+            };
+
+            let (to_target, distance) = match target {
+                Some(target) if target != own_center => {
+                    let delta = target - own_center;
+                    (delta / delta.magnitude(), delta.magnitude())
+                }
+                _ => (Vec2::zero(), 0.0),
+            };
+            // Rotated a quarter turn to fly around the target
+            let tangent = Vec2::new(-to_target.y, to_target.x);
+            // Move towards the orbit when too far away and away from it when too close
+            let orbit = tangent + to_target * ((distance - *ORBIT_RADIUS) / *ORBIT_RADIUS);
+
+            let desired = match behaviour {
+                Behaviour::Wander => {
+                    ai.wander_angle += rng.gen_range(-1.0, 1.0) * *WANDER_JITTER * dt;
+
+                    // Wandering is a bit slower
+                    Vec2::new(ai.wander_angle.cos(), ai.wander_angle.sin()) * *ENEMY_SPEED / 2.0
+                }
+                Behaviour::Seek => to_target * *ENEMY_SPEED,
+                Behaviour::Flee => -to_target * *ENEMY_SPEED,
+                Behaviour::Orbit | Behaviour::StrafeAndShoot => {
+                    orbit.try_normalized().unwrap_or(tangent) * *ENEMY_SPEED
+                }
+            };
+
+            // Steer towards the desired velocity instead of changing it instantly
+            vel.0 += (desired - vel.0) * (*STEERING * dt).min(1.0);
+
+            if let Some(gun) = gun.get_mut(entity) {
+                match target {
+                    Some(target)
+                        if behaviour == Behaviour::StrafeAndShoot && distance < gun.strength =>
+                    {
+                        // Don't be perfectly accurate
+                        let error = Vec2::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0))
+                            * *AIM_ERROR;
+
+                        gun.aim = Some(target + error);
+                        gun.trigger = true;
+                    }
+                    _ => gun.trigger = false,
+                }
+            }
+        }
+    }
+}
@@ -1,72 +1,495 @@
 use crate::{
+    collision::{Collider, CollisionEvent},
+    game::{self, GameState, Invulnerable, Score},
+    particle::{self, ASTEROID_EXPLOSION_COLOR},
     physics::*,
-    sprite::generate,
-    user::{MovesWithCamera, RotatesWithCamera},
+    powerup::{self, DropChance, PowerUpSprites},
+    projectile::{damage_source, Laser, Projectile},
+    random::{Seed, WorldRng},
+    sprite::{self, generate},
+    user::{Camera, MovesWithCamera, Player, RotatesWithCamera},
 };
 use anyhow::Result;
-use rand::prelude::*;
-use specs::{prelude::*, Component, DenseVecStorage};
-use specs_blit::Sprite;
-use sprite_gen::{MaskValue::*, Options};
+use rand::{prelude::*, rngs::StdRng};
+use specs::{prelude::*, shrev::EventChannel, Component, DenseVecStorage};
+use specs_blit::{Sprite, SpriteRef};
+use sprite_gen::{MaskValue, MaskValue::*, Options};
+use std::collections::HashSet;
 
-#[derive(Component, Debug, Default)]
-pub struct Asteroid {}
+type Vec2 = vek::Vec2<f64>;
 
+#[const_tweaker::tweak(min = 0.0, max = 100.0, step = 1.0)]
+const FRAGMENT_SPEED: f64 = 15.0;
+#[const_tweaker::tweak(min = 0.0, max = 3.0, step = 0.01)]
+const FRAGMENT_SPREAD: f64 = 0.8;
+
+/// The width and height of a chunk of the streamed asteroid field.
+const CHUNK_SIZE: f64 = 400.0;
+/// Chunks within this many chunks from the chunk the camera is in are spawned.
+const LOAD_RADIUS: i32 = 2;
+/// Chunks and asteroids further away than this many chunks are despawned, it's bigger than the load
+/// radius so objects at the border don't keep popping in and out.
+const UNLOAD_RADIUS: i32 = 3;
+/// The maximum amount of asteroids spawned in a chunk.
+const ASTEROIDS_PER_CHUNK: usize = 4;
+/// Amount of different sprites the asteroids of every size pick from, new sprites are never freed
+/// so they are reused.
+const SPRITE_POOL_SIZE: usize = 16;
+
+const OPTIONS: Options = Options {
+    mirror_x: false,
+    mirror_y: false,
+    colored: true,
+    edge_brightness: 0.3,
+    color_variations: 0.2,
+    brightness_noise: 0.3,
+    saturation: 0.5,
+};
+
+const LARGE_WIDTH: usize = 11;
+const LARGE_MASK: [MaskValue; 121] = [
+    Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty,
+    Empty, Empty, Body1, Body1, Body1, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Body1,
+    Body1, Body1, Body1, Body1, Empty, Empty, Empty, Empty, Empty, Body1, Body1, Body1, Solid,
+    Body1, Body1, Body1, Empty, Empty, Empty, Body1, Body1, Body1, Solid, Solid, Solid, Body1,
+    Body1, Body1, Empty, Empty, Body1, Body1, Solid, Solid, Solid, Solid, Solid, Body1, Body1,
+    Empty, Empty, Body1, Body1, Body1, Solid, Solid, Solid, Body1, Body1, Body1, Empty, Empty,
+    Empty, Body1, Body1, Body1, Solid, Body1, Body1, Body1, Empty, Empty, Empty, Empty, Empty,
+    Body1, Body1, Body1, Body1, Body1, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Body1,
+    Body1, Body1, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty,
+    Empty, Empty, Empty, Empty,
+];
+
+const MEDIUM_WIDTH: usize = 7;
+const MEDIUM_MASK: [MaskValue; 49] = [
+    Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Body1, Body1, Body1, Empty,
+    Empty, Empty, Body1, Body1, Solid, Body1, Body1, Empty, Empty, Body1, Solid, Solid, Solid,
+    Body1, Empty, Empty, Body1, Body1, Solid, Body1, Body1, Empty, Empty, Empty, Body1, Body1,
+    Body1, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty,
+];
+
+const SMALL_WIDTH: usize = 5;
+const SMALL_MASK: [MaskValue; 25] = [
+    Empty, Empty, Empty, Empty, Empty, Empty, Body1, Body1, Body1, Empty, Empty, Body1, Solid,
+    Body1, Empty, Empty, Body1, Body1, Body1, Empty, Empty, Empty, Empty, Empty, Empty,
+];
+
+/// The size tier of an asteroid, bigger asteroids split into smaller ones when destroyed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsteroidSize {
+    Large,
+    Medium,
+    Small,
+}
+
+impl AsteroidSize {
+    /// The tier the fragments will have when this asteroid is destroyed.
+    pub fn smaller(self) -> Option<Self> {
+        match self {
+            AsteroidSize::Large => Some(AsteroidSize::Medium),
+            AsteroidSize::Medium => Some(AsteroidSize::Small),
+            AsteroidSize::Small => None,
+        }
+    }
+
+    /// How much damage an asteroid of this size can take.
+    pub fn health(self) -> f64 {
+        match self {
+            AsteroidSize::Large => 3.0,
+            AsteroidSize::Medium => 2.0,
+            AsteroidSize::Small => 1.0,
+        }
+    }
+
+    /// The score for destroying an asteroid of this size, smaller ones are harder to hit.
+    pub fn points(self) -> u64 {
+        match self {
+            AsteroidSize::Large => 20,
+            AsteroidSize::Medium => 50,
+            AsteroidSize::Small => 100,
+        }
+    }
+
+    /// The amount of particles spawned when an asteroid of this size is destroyed.
+    pub fn particles(self) -> usize {
+        match self {
+            AsteroidSize::Large => 24,
+            AsteroidSize::Medium => 16,
+            AsteroidSize::Small => 10,
+        }
+    }
+
+    /// The width of the mask and the mask used to generate the sprite.
+    fn mask(self) -> (usize, &'static [MaskValue]) {
+        match self {
+            AsteroidSize::Large => (LARGE_WIDTH, &LARGE_MASK),
+            AsteroidSize::Medium => (MEDIUM_WIDTH, &MEDIUM_MASK),
+            AsteroidSize::Small => (SMALL_WIDTH, &SMALL_MASK),
+        }
+    }
+
+    /// Generate a new sprite for this size and a collider matching it.
+    fn generate<R: Rng>(self, rng: &mut R) -> Result<(SpriteRef, Collider)> {
+        let (width, mask) = self.mask();
+
+        let sprite = generate(width, OPTIONS, mask, 8, rng)?;
+        let collider = Collider::circle(sprite::size(width, OPTIONS, mask));
+
+        Ok((sprite, collider))
+    }
+}
+
+/// Pregenerated sprites with their colliders for every asteroid size, generating them while
+/// playing would cause hitches.
+pub struct AsteroidSprites {
+    large: Vec<(SpriteRef, Collider)>,
+    medium: Vec<(SpriteRef, Collider)>,
+    small: Vec<(SpriteRef, Collider)>,
+}
+
+impl AsteroidSprites {
+    /// Generate the sprites for all sizes.
+    pub fn new(rng: &mut WorldRng) -> Result<Self> {
+        let mut pool = |size: AsteroidSize| {
+            (0..SPRITE_POOL_SIZE)
+                .map(|_| size.generate(rng))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        Ok(Self {
+            large: pool(AsteroidSize::Large)?,
+            medium: pool(AsteroidSize::Medium)?,
+            small: pool(AsteroidSize::Small)?,
+        })
+    }
+
+    /// Pick a random sprite with its collider for the size.
+    pub fn pick<R: Rng>(&self, size: AsteroidSize, rng: &mut R) -> (SpriteRef, Collider) {
+        let pool = match size {
+            AsteroidSize::Large => &self.large,
+            AsteroidSize::Medium => &self.medium,
+            AsteroidSize::Small => &self.small,
+        };
+
+        pool[rng.gen_range(0, pool.len())].clone()
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct Asteroid {
+    pub size: AsteroidSize,
+    pub health: f64,
+}
+
+impl Asteroid {
+    /// Instantiate a new asteroid with full health.
+    pub fn new(size: AsteroidSize) -> Self {
+        Self {
+            size,
+            health: size.health(),
+        }
+    }
+}
+
+/// Spawn large asteroids, the speed is a multiplier for their random velocity.
 pub fn spawn_asteroids(
     world: &mut World,
     amount: usize,
-    screen_width: usize,
-    screen_height: usize,
+    speed: f64,
+    world_width: usize,
+    world_height: usize,
 ) -> Result<()> {
-    let (width, _height, options) = (
-        11,
-        11,
-        Options {
-            mirror_x: false,
-            mirror_y: false,
-            colored: true,
-            edge_brightness: 0.3,
-            color_variations: 0.2,
-            brightness_noise: 0.3,
-            saturation: 0.5,
-        },
-    );
-    let asteroid_mask = [
-        Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty,
-        Empty, Empty, Body1, Body1, Body1, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Body1,
-        Body1, Body1, Body1, Body1, Empty, Empty, Empty, Empty, Empty, Body1, Body1, Body1, Solid,
-        Body1, Body1, Body1, Empty, Empty, Empty, Body1, Body1, Body1, Solid, Solid, Solid, Body1,
-        Body1, Body1, Empty, Empty, Body1, Body1, Solid, Solid, Solid, Solid, Solid, Body1, Body1,
-        Empty, Empty, Body1, Body1, Body1, Solid, Solid, Solid, Body1, Body1, Body1, Empty, Empty,
-        Empty, Body1, Body1, Body1, Solid, Body1, Body1, Body1, Empty, Empty, Empty, Empty, Empty,
-        Body1, Body1, Body1, Body1, Body1, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Body1,
-        Body1, Body1, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty, Empty,
-        Empty, Empty, Empty, Empty,
-    ];
-
-    let mut rng = rand::thread_rng();
-
     for _ in 0..amount {
-        // Generate the sprite
-        let sprite = generate(width, options, &asteroid_mask, 8)?;
+        // Generate the sprite and the placement
+        let (sprite, collider, pos, vel) = {
+            let sprites = world.read_resource::<AsteroidSprites>();
+            let mut rng = world.write_resource::<WorldRng>();
+
+            let (sprite, collider) = sprites.pick(AsteroidSize::Large, &mut *rng);
+            let pos = Position::new(
+                rng.gen_range(0, world_width) as f64,
+                rng.gen_range(0, world_height) as f64,
+            );
+            let vel = Velocity::new(
+                rng.gen_range(-10.0, 10.0) * speed,
+                rng.gen_range(-10.0, 10.0) * speed,
+            );
+
+            (sprite, collider, pos, vel)
+        };
 
         // Add the entity to the ECS system
         world
             .create_entity()
-            .with(Asteroid::default())
-            .with(Position::new(
-                rng.gen_range(0, screen_width) as f64,
-                rng.gen_range(0, screen_height) as f64,
-            ))
-            .with(Velocity::new(
-                rng.gen_range(-10.0, 10.0),
-                rng.gen_range(-10.0, 10.0),
-            ))
+            .with(Asteroid::new(AsteroidSize::Large))
+            .with(pos)
+            .with(vel)
             .with(MovesWithCamera)
             .with(RotatesWithCamera)
             .with(Sprite::new(sprite))
+            .with(collider)
             .build();
     }
 
     Ok(())
 }
+
+/// An endless asteroid field that is spawned in chunks around the camera.
+///
+/// Every chunk is generated from the seed and its coordinates, so the same coordinates always
+/// contain the same asteroids.
+pub struct AsteroidField {
+    seed: u64,
+    /// The chunks that currently have their asteroids spawned.
+    loaded: HashSet<(i32, i32)>,
+}
+
+impl AsteroidField {
+    /// Instantiate a new field, nothing is spawned until the system runs.
+    pub fn new(seed: Seed) -> Self {
+        Self {
+            seed: seed.0,
+            loaded: HashSet::new(),
+        }
+    }
+
+    /// Forget which chunks are spawned, so they are spawned again when the asteroids are gone.
+    pub fn reset(&mut self) {
+        self.loaded.clear();
+    }
+
+    /// The chunk coordinates containing the position.
+    fn chunk(pos: Vec2) -> (i32, i32) {
+        (
+            (pos.x / CHUNK_SIZE).floor() as i32,
+            (pos.y / CHUNK_SIZE).floor() as i32,
+        )
+    }
+
+    /// A random generator that always gives the same results for the same chunk.
+    fn chunk_rng(&self, (x, y): (i32, i32)) -> StdRng {
+        let hash = self.seed
+            ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+
+        StdRng::seed_from_u64(hash)
+    }
+}
+
+/// Spawn and despawn the chunks of the asteroid field around the camera.
+///
+/// Does nothing when there's no `AsteroidField` resource.
+pub struct AsteroidFieldSystem;
+impl<'a> System<'a> for AsteroidFieldSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, LazyUpdate>,
+        Read<'a, Camera>,
+        Option<Write<'a, AsteroidField>>,
+        Option<Read<'a, AsteroidSprites>>,
+        ReadStorage<'a, Asteroid>,
+        ReadStorage<'a, Position>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, updater, camera, field, sprites, asteroid, pos): Self::SystemData,
+    ) {
+        let (mut field, sprites) = match (field, sprites) {
+            (Some(field), Some(sprites)) => (field, sprites),
+            _ => return,
+        };
+
+        let center = AsteroidField::chunk(camera.center());
+        let in_radius = |(x, y): (i32, i32), radius: i32| {
+            (x - center.0).abs() <= radius && (y - center.1).abs() <= radius
+        };
+
+        // Despawn everything that is too far away, this includes the fragments
+        for (entity, _, pos) in (&entities, &asteroid, &pos).join() {
+            if !in_radius(AsteroidField::chunk(pos.0), UNLOAD_RADIUS) {
+                let _ = entities.delete(entity);
+            }
+        }
+        field
+            .loaded
+            .retain(|chunk| in_radius(*chunk, UNLOAD_RADIUS));
+
+        // Spawn the chunks that came into range
+        for y in center.1 - LOAD_RADIUS..=center.1 + LOAD_RADIUS {
+            for x in center.0 - LOAD_RADIUS..=center.0 + LOAD_RADIUS {
+                if !field.loaded.insert((x, y)) {
+                    continue;
+                }
+
+                let mut rng = field.chunk_rng((x, y));
+                for _ in 0..rng.gen_range(0, ASTEROIDS_PER_CHUNK + 1) {
+                    let pos = Vec2::new(
+                        (x as f64 + rng.gen::<f64>()) * CHUNK_SIZE,
+                        (y as f64 + rng.gen::<f64>()) * CHUNK_SIZE,
+                    );
+                    let vel = Vec2::new(rng.gen_range(-10.0, 10.0), rng.gen_range(-10.0, 10.0));
+                    let (sprite, collider) = sprites.pick(AsteroidSize::Large, &mut rng);
+
+                    updater
+                        .create_entity(&entities)
+                        .with(Asteroid::new(AsteroidSize::Large))
+                        .with(Position(pos))
+                        .with(Velocity(vel))
+                        .with(MovesWithCamera)
+                        .with(RotatesWithCamera)
+                        .with(Sprite::new(sprite))
+                        .with(collider)
+                        .build();
+                }
+            }
+        }
+    }
+}
+
+/// Damage asteroids hit by lasers and projectiles and split them into smaller fragments when they are destroyed.
+///
+/// The player crashing into an asteroid destroys it as if the player shot it.
+#[derive(Default)]
+pub struct AsteroidSplitSystem {
+    reader: Option<ReaderId<CollisionEvent>>,
+}
+impl<'a> System<'a> for AsteroidSplitSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        Read<'a, LazyUpdate>,
+        Read<'a, DropChance>,
+        ReadExpect<'a, PowerUpSprites>,
+        ReadExpect<'a, AsteroidSprites>,
+        Write<'a, WorldRng>,
+        Write<'a, Score>,
+        Read<'a, GameState>,
+        ReadStorage<'a, Laser>,
+        ReadStorage<'a, Projectile>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Invulnerable>,
+        WriteStorage<'a, Asteroid>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Collider>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            events,
+            updater,
+            chance,
+            powerup_sprites,
+            sprites,
+            mut rng,
+            mut score,
+            state,
+            laser,
+            projectile,
+            player,
+            invulnerable,
+            mut asteroid,
+            pos,
+            vel,
+            collider,
+        ): Self::SystemData,
+    ) {
+        for event in events.read(self.reader.as_mut().unwrap()) {
+            // Only handle lasers, projectiles and the player hitting asteroids
+            let (owner, target, damage) = match damage_source(event.entity, &laser, &projectile) {
+                Some((owner, damage)) => (owner, event.other, damage),
+                None => match game::crash(event, *state, &player, &invulnerable) {
+                    // Crashing always destroys the asteroid
+                    Some((player, other)) => (player, other, f64::INFINITY),
+                    None => continue,
+                },
+            };
+            let asteroid = match asteroid.get_mut(target) {
+                // The asteroid might already be destroyed by another laser this frame
+                Some(asteroid) if asteroid.health > 0.0 => asteroid,
+                _ => continue,
+            };
+
+            asteroid.health -= damage;
+            if asteroid.health > 0.0 {
+                continue;
+            }
+
+            let _ = entities.delete(target);
+            // Enemies can destroy asteroids as well
+            let destroyed_by_player = player.contains(owner);
+            if destroyed_by_player {
+                score.0 += asteroid.size.points();
+            }
+
+            let (pos, vel, offset) = match (pos.get(target), vel.get(target), collider.get(target))
+            {
+                (Some(pos), Some(vel), Some(collider)) => (pos.0, vel.0, collider.offset),
+                _ => continue,
+            };
+            let center = pos + offset;
+
+            particle::explosion(
+                &entities,
+                &updater,
+                &mut *rng,
+                center,
+                vel,
+                asteroid.size.particles(),
+                ASTEROID_EXPLOSION_COLOR,
+            );
+            if destroyed_by_player {
+                powerup::maybe_drop(
+                    &entities,
+                    &updater,
+                    &mut *rng,
+                    &powerup_sprites,
+                    *chance,
+                    center,
+                    vel,
+                );
+            }
+
+            // The smallest asteroids disappear
+            let size = match asteroid.size.smaller() {
+                Some(size) => size,
+                None => continue,
+            };
+
+            for _ in 0..rng.gen_range(2, 4) {
+                let (sprite, collider) = sprites.pick(size, &mut *rng);
+
+                // Push the fragment away from the parent in a slightly different direction
+                let angle = rng.gen_range(-*FRAGMENT_SPREAD, *FRAGMENT_SPREAD);
+                let (sin, cos) = angle.sin_cos();
+                let dir = Vec2::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0));
+                let vel = Vec2::new(vel.x * cos - vel.y * sin, vel.x * sin + vel.y * cos)
+                    + dir * *FRAGMENT_SPEED;
+
+                updater
+                    .create_entity(&entities)
+                    .with(Asteroid::new(size))
+                    .with(Position(center - collider.offset + dir * collider.offset))
+                    .with(Velocity(vel))
+                    .with(MovesWithCamera)
+                    .with(RotatesWithCamera)
+                    .with(Sprite::new(sprite))
+                    .with(collider)
+                    .build();
+            }
+        }
+    }
+}
@@ -33,6 +33,12 @@ pub struct Audio {
     mixer: Arc<Mutex<Mixer>>,
 }
 
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Audio {
     /// Instantiate a new audio object without a mixer.
     pub fn new() -> Self {
@@ -77,6 +83,60 @@ impl Audio {
         self.play(sample, 1.0);
     }
 
+    /// Play a short bullet sound.
+    pub fn play_bullet(&mut self) {
+        let mut sample = Sample::default();
+        sample.osc_type(OscillatorType::Square);
+        sample.osc_frequency(600);
+        sample.env_attack(0.01);
+        sample.env_decay(0.05);
+        sample.env_sustain(0.3);
+        sample.env_release(0.05);
+
+        self.play(sample, 1.0);
+    }
+
+    /// Play a missile launch sound.
+    pub fn play_missile(&mut self) {
+        let mut sample = Sample::default();
+        sample.osc_type(OscillatorType::Noise);
+        sample.osc_frequency(300);
+        sample.env_attack(0.05);
+        sample.env_decay(0.3);
+        sample.env_sustain(0.4);
+        sample.env_release(0.4);
+        sample.dis_crunch(0.2);
+
+        self.play(sample, 1.0);
+    }
+
+    /// Play a beam sound, a fully charged beam with a charge of 1 sounds higher.
+    pub fn play_beam(&mut self, charge: f64) {
+        let mut sample = Sample::default();
+        sample.osc_type(OscillatorType::Sine);
+        sample.osc_frequency(400 + (charge.clamp(0.0, 1.0) * 800.0) as usize);
+        sample.env_attack(0.05);
+        sample.env_decay(0.3);
+        sample.env_sustain(0.6);
+        sample.env_release(0.5);
+        sample.dis_drive(0.5);
+
+        self.play(sample, 1.0);
+    }
+
+    /// Play a sound for dropping a mine.
+    pub fn play_mine(&mut self) {
+        let mut sample = Sample::default();
+        sample.osc_type(OscillatorType::Square);
+        sample.osc_frequency(150);
+        sample.env_attack(0.01);
+        sample.env_decay(0.1);
+        sample.env_sustain(0.5);
+        sample.env_release(0.3);
+
+        self.play(sample, 1.0);
+    }
+
     /// Play a sample.
     pub fn play(&mut self, sample: Sample, _volume: f32) {
         self.mixer.lock().unwrap().play(sample);
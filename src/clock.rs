@@ -0,0 +1,97 @@
+use std::time::Instant;
+
+/// How often the physics are updated per second.
+pub const PHYSICS_RATE: f64 = 60.0;
+
+/// The longest frame time that will be simulated, slower frames will slow down the game instead
+/// of making every next frame even slower by trying to catch up.
+const MAX_FRAME_TIME: f64 = 0.25;
+
+/// The maximum amount of physics steps that will be run in a single frame.
+const MAX_STEPS: usize = 8;
+
+/// How often the measured frame rate is updated in seconds.
+const FRAME_RATE_INTERVAL: f64 = 0.5;
+
+/// The frames drawn per second, shown on the HUD when it's set.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrameRate(pub f64);
+
+/// Measures the real time between frames and divides it into fixed physics steps.
+#[derive(Debug)]
+pub struct FrameClock {
+    /// The moment the previous frame started.
+    last: Instant,
+    /// The time that still needs to be simulated.
+    accumulator: f64,
+    /// The fixed time of a single physics step.
+    step: f64,
+    /// Frames counted since the frame rate was last measured.
+    frames: usize,
+    /// Seconds passed since the frame rate was last measured.
+    elapsed: f64,
+    /// The most recently measured frame rate.
+    frame_rate: f64,
+}
+
+impl Default for FrameClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameClock {
+    /// Instantiate a new clock starting now.
+    pub fn new() -> Self {
+        Self {
+            last: Instant::now(),
+            accumulator: 0.0,
+            step: 1.0 / PHYSICS_RATE,
+            frames: 0,
+            elapsed: 0.0,
+            frame_rate: 0.0,
+        }
+    }
+
+    /// Measure the time since the previous tick and return how many physics steps need to be run.
+    pub fn tick(&mut self) -> usize {
+        let now = Instant::now();
+        let real_frame_time = (now - self.last).as_secs_f64();
+        let frame_time = real_frame_time.min(MAX_FRAME_TIME);
+        self.last = now;
+
+        // Average the frame rate over a while so it's readable
+        self.frames += 1;
+        self.elapsed += real_frame_time;
+        if self.elapsed >= FRAME_RATE_INTERVAL {
+            self.frame_rate = self.frames as f64 / self.elapsed;
+            self.frames = 0;
+            self.elapsed = 0.0;
+        }
+
+        self.accumulator += frame_time;
+
+        let steps = (self.accumulator / self.step) as usize;
+        self.accumulator -= steps as f64 * self.step;
+
+        if steps > MAX_STEPS {
+            // Drop the time that can't be caught up with
+            self.accumulator = 0.0;
+
+            MAX_STEPS
+        } else {
+            steps
+        }
+    }
+
+    /// The frames per second measured over the last interval.
+    pub fn frame_rate(&self) -> FrameRate {
+        FrameRate(self.frame_rate)
+    }
+
+    /// How far the current moment is between the previous and the next physics step, between 0
+    /// and 1.
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.step).min(1.0)
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{
+    asteroid::Asteroid,
+    physics::{Position, WorldBounds},
+    projectile::Laser,
+};
+use specs::{prelude::*, shrev::EventChannel, Component, DenseVecStorage};
+
+type Vec2 = vek::Vec2<f64>;
+
+/// The geometric shape of a collider.
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    /// Circle with a radius.
+    Circle(f64),
+    /// Axis-aligned bounding box with half the width and height.
+    Aabb(Vec2),
+}
+
+impl Shape {
+    /// Check whether this shape overlaps with another shape.
+    pub fn overlaps(&self, center: Vec2, other: &Shape, other_center: Vec2) -> bool {
+        match (*self, *other) {
+            (Shape::Circle(r1), Shape::Circle(r2)) => {
+                center.distance_squared(other_center) <= (r1 + r2) * (r1 + r2)
+            }
+            (Shape::Aabb(h1), Shape::Aabb(h2)) => {
+                let delta = (center - other_center).map(f64::abs);
+                delta.x <= h1.x + h2.x && delta.y <= h1.y + h2.y
+            }
+            (Shape::Circle(radius), Shape::Aabb(half)) => {
+                circle_overlaps_aabb(center, radius, other_center, half)
+            }
+            (Shape::Aabb(half), Shape::Circle(radius)) => {
+                circle_overlaps_aabb(other_center, radius, center, half)
+            }
+        }
+    }
+
+    /// Cast a ray from an origin with a normalized direction and return the distance at which it
+    /// enters this shape, if it does so within the maximum distance.
+    pub fn raycast(&self, center: Vec2, origin: Vec2, dir: Vec2, max: f64) -> Option<f64> {
+        let distance = match *self {
+            Shape::Circle(radius) => {
+                let delta = origin - center;
+                let b = delta.dot(dir);
+                let c = delta.magnitude_squared() - radius * radius;
+                if c > 0.0 && b > 0.0 {
+                    // The origin is outside of the circle and pointing away from it
+                    return None;
+                }
+
+                let discriminant = b * b - c;
+                if discriminant < 0.0 {
+                    return None;
+                }
+
+                // Start at zero when the origin is inside the circle
+                (-b - discriminant.sqrt()).max(0.0)
+            }
+            Shape::Aabb(half) => {
+                let (min, max) = (center - half, center + half);
+
+                // Use the slab method for both axes
+                let mut near = 0.0f64;
+                let mut far = f64::INFINITY;
+                for (o, d, min, max) in &[
+                    (origin.x, dir.x, min.x, max.x),
+                    (origin.y, dir.y, min.y, max.y),
+                ] {
+                    if d.abs() < f64::EPSILON {
+                        if o < min || o > max {
+                            return None;
+                        }
+                    } else {
+                        let t1 = (min - o) / d;
+                        let t2 = (max - o) / d;
+                        near = near.max(t1.min(t2));
+                        far = far.min(t1.max(t2));
+                    }
+                }
+
+                if near > far {
+                    return None;
+                }
+
+                near
+            }
+        };
+
+        if distance <= max {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+}
+
+/// Check whether a circle overlaps with a box by clamping the center of the circle to the box.
+fn circle_overlaps_aabb(center: Vec2, radius: f64, box_center: Vec2, half: Vec2) -> bool {
+    let closest = Vec2::new(
+        center
+            .x
+            .max(box_center.x - half.x)
+            .min(box_center.x + half.x),
+        center
+            .y
+            .max(box_center.y - half.y)
+            .min(box_center.y + half.y),
+    );
+
+    center.distance_squared(closest) <= radius * radius
+}
+
+/// Object that can collide with other colliders and lasers.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Collider {
+    /// The shape used for the overlap tests.
+    pub shape: Shape,
+    /// The offset from the position to the center of the shape.
+    pub offset: Vec2,
+}
+
+impl Collider {
+    /// Create a circle fitting inside a sprite with the size.
+    pub fn circle(size: Vec2) -> Self {
+        Self {
+            shape: Shape::Circle(size.x.min(size.y) / 2.0),
+            offset: size / 2.0,
+        }
+    }
+
+    /// Create a box covering a sprite with the size.
+    pub fn aabb(size: Vec2) -> Self {
+        Self {
+            shape: Shape::Aabb(size / 2.0),
+            offset: size / 2.0,
+        }
+    }
+}
+
+/// The first object hit by a ray.
+#[derive(Debug, Clone, Copy)]
+pub struct RaycastHit {
+    /// The object that got hit.
+    pub entity: Entity,
+    /// The point in world coordinates where the ray entered the object.
+    pub point: Vec2,
+    /// The distance from the origin of the ray to the point.
+    pub distance: f64,
+}
+
+/// Cast a ray from an origin in a normalized direction and return the nearest object hit within
+/// the maximum distance, the ignored entity is skipped.
+///
+/// Objects on the other side of the world seam are hit as well.
+pub fn raycast<'a>(
+    colliders: impl IntoIterator<Item = (Entity, &'a Position, &'a Collider)>,
+    bounds: &WorldBounds,
+    origin: Vec2,
+    dir: Vec2,
+    max: f64,
+    ignore: Entity,
+) -> Option<RaycastHit> {
+    colliders
+        .into_iter()
+        .filter(|(entity, _, _)| *entity != ignore)
+        .filter_map(|(entity, pos, collider)| {
+            collider
+                .shape
+                .raycast(
+                    bounds.nearest(pos.0 + collider.offset, origin),
+                    origin,
+                    dir,
+                    max,
+                )
+                .map(|distance| RaycastHit {
+                    entity,
+                    point: origin + dir * distance,
+                    distance,
+                })
+        })
+        .min_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap())
+}
+
+/// Event emitted when two objects overlap or a laser hits an object.
+#[derive(Debug, Clone, Copy)]
+pub struct CollisionEvent {
+    /// The object that caused the collision, this is the laser when one is involved.
+    pub entity: Entity,
+    /// The object that got hit.
+    pub other: Entity,
+    /// The point in world coordinates where the collision happened.
+    pub point: Vec2,
+}
+
+pub struct CollisionSystem;
+impl<'a> System<'a> for CollisionSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, WorldBounds>,
+        Write<'a, EventChannel<CollisionEvent>>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, Asteroid>,
+        WriteStorage<'a, Laser>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, bounds, mut events, pos, collider, asteroid, mut laser): Self::SystemData,
+    ) {
+        // Asteroids crashing into each other don't do anything, so they are only tested against the
+        // other objects, there are a lot more of them
+        let (asteroids, others): (Vec<_>, Vec<_>) = (&entities, &pos, &collider)
+            .join()
+            .map(|(entity, pos, collider)| (entity, pos.0 + collider.offset, collider.shape))
+            .partition(|(entity, _, _)| asteroid.contains(*entity));
+
+        for (index, (entity, center, shape)) in others.iter().enumerate() {
+            for (other, other_center, other_shape) in
+                others.iter().skip(index + 1).chain(asteroids.iter())
+            {
+                // Objects can also touch across the world seam
+                let other_center = &bounds.nearest(*other_center, *center);
+
+                if shape.overlaps(*center, other_shape, *other_center) {
+                    events.single_write(CollisionEvent {
+                        entity: *entity,
+                        other: *other,
+                        point: (*center + *other_center) / 2.0,
+                    });
+                }
+            }
+        }
+
+        // Report the hits of the lasers that have just been fired, the beam has already been cast
+        for (entity, laser) in (&entities, &mut laser).join() {
+            if laser.resolved {
+                continue;
+            }
+            laser.resolved = true;
+
+            if let Some(hit) = laser.hit {
+                events.single_write(CollisionEvent {
+                    entity,
+                    other: hit.entity,
+                    point: hit.point,
+                });
+            }
+        }
+    }
+}
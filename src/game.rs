@@ -0,0 +1,373 @@
+use crate::{
+    asteroid::Asteroid,
+    collision::{Collider, CollisionEvent},
+    input::{Action, InputState},
+    particle::{self, ROCKET_EXPLOSION_COLOR},
+    physics::{DeltaTime, Position, Velocity},
+    projectile::{damage_source, Laser, Projectile},
+    random::WorldRng,
+    rocket::Rocket,
+    user::Player,
+};
+use specs::{prelude::*, shrev::EventChannel, storage::MaskedStorage, Component, DenseVecStorage};
+use specs_blit::Sprite;
+use std::ops::Deref;
+
+/// The amount of lives the player starts with.
+pub const START_LIVES: u32 = 3;
+
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const INVULNERABLE_TIME: f64 = 2.0;
+/// The invulnerability after taking damage, shorter than after losing a life.
+#[const_tweaker::tweak(min = 0.0, max = 5.0, step = 0.01)]
+const HIT_INVULNERABLE_TIME: f64 = 0.3;
+/// How many times per second invulnerable objects flash.
+#[const_tweaker::tweak(min = 0.0, max = 30.0, step = 0.5)]
+const FLASH_RATE: f64 = 10.0;
+/// Shield points regenerated every second.
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const SHIELD_REGEN: f64 = 1.0;
+/// Seconds after taking damage before the shield starts regenerating.
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const SHIELD_REGEN_DELAY: f64 = 3.0;
+/// Damage for every unit of speed objects crash into each other with.
+#[const_tweaker::tweak(min = 0.0, max = 1.0, step = 0.01)]
+const COLLISION_DAMAGE: f64 = 0.1;
+/// The least damage a crash does, even when barely touching.
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const MIN_COLLISION_DAMAGE: f64 = 1.0;
+
+/// The phase the game is in, every state runs different systems.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GameState {
+    /// Waiting for the player to start, the world keeps drifting in the background.
+    #[default]
+    Title,
+    Playing,
+    /// Nothing moves until the game is resumed.
+    Paused,
+    /// The player has no lives left, the world keeps drifting in the background.
+    GameOver,
+}
+
+impl GameState {
+    /// The state after the input changed from the previous to the current input.
+    ///
+    /// Starting a new game after a game over is handled by `rocket_game::step` because it needs to
+    /// respawn everything.
+    pub fn next(self, input: &InputState, previous: &InputState) -> Self {
+        let pressed = |action| input.pressed(action) && !previous.pressed(action);
+
+        match self {
+            GameState::Title if pressed(Action::Fire) => GameState::Playing,
+            GameState::Playing if pressed(Action::Pause) => GameState::Paused,
+            GameState::Paused if pressed(Action::Pause) => GameState::Playing,
+            state => state,
+        }
+    }
+}
+
+/// The input of the previous physics step, used to find out which actions were just pressed.
+#[derive(Debug, Default)]
+pub struct PreviousInput(pub InputState);
+
+/// Points gained by destroying asteroids and enemy rockets.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Score(pub u64);
+
+/// How many more times the player can get hit, the game is over when there are none left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lives(pub u32);
+
+impl Default for Lives {
+    fn default() -> Self {
+        Self(START_LIVES)
+    }
+}
+
+/// Object that can't get hit until the time is up.
+#[derive(Component, Debug)]
+pub struct Invulnerable(pub f64);
+
+impl Invulnerable {
+    /// Instantiate a new invulnerability for the default time.
+    pub fn new() -> Self {
+        Self(*INVULNERABLE_TIME)
+    }
+
+    /// Whether the object is hidden at this moment of flashing.
+    pub fn hidden(&self) -> bool {
+        (self.0 * *FLASH_RATE * 2.0) as i64 % 2 == 1
+    }
+}
+
+impl Default for Invulnerable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct InvulnerableSystem;
+impl<'a> System<'a> for InvulnerableSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        WriteStorage<'a, Invulnerable>,
+    );
+
+    fn run(&mut self, (entities, dt, mut invulnerable): Self::SystemData) {
+        let dt = dt.to_seconds();
+
+        let mut expired = vec![];
+        for (entity, invulnerable) in (&entities, &mut invulnerable).join() {
+            invulnerable.0 -= dt;
+            if invulnerable.0 <= 0.0 {
+                expired.push(entity);
+            }
+        }
+
+        for entity in expired {
+            invulnerable.remove(entity);
+        }
+    }
+}
+
+/// Hide the sprites of invulnerable objects every other flash.
+///
+/// It must run after the sprite positions are set.
+pub struct FlashSystem;
+impl<'a> System<'a> for FlashSystem {
+    type SystemData = (ReadStorage<'a, Invulnerable>, WriteStorage<'a, Sprite>);
+
+    fn run(&mut self, (invulnerable, mut sprite): Self::SystemData) {
+        for (invulnerable, sprite) in (&invulnerable, &mut sprite).join() {
+            if invulnerable.hidden() {
+                // Move the sprite far outside of the screen so it isn't drawn
+                sprite.set_pos(i32::MIN / 2, i32::MIN / 2);
+            }
+        }
+    }
+}
+
+/// How much damage an object can take before it's destroyed.
+#[derive(Component, Debug)]
+pub struct Health {
+    pub current: f64,
+    pub max: f64,
+}
+
+impl Health {
+    /// Instantiate a new full health.
+    pub fn new(max: f64) -> Self {
+        Self { current: max, max }
+    }
+}
+
+/// Absorbs damage before the health is affected, it regenerates when not taking damage for a
+/// while.
+#[derive(Component, Debug)]
+pub struct Shield {
+    pub current: f64,
+    pub max: f64,
+    /// Seconds left before the shield starts regenerating.
+    pub regen_delay: f64,
+}
+
+impl Shield {
+    /// Instantiate a new fully charged shield.
+    pub fn new(max: f64) -> Self {
+        Self {
+            current: max,
+            max,
+            regen_delay: 0.0,
+        }
+    }
+
+    /// Take as much of the damage as possible, returns the damage that went through.
+    pub fn absorb(&mut self, damage: f64) -> f64 {
+        self.regen_delay = *SHIELD_REGEN_DELAY;
+
+        let absorbed = damage.min(self.current);
+        self.current -= absorbed;
+
+        damage - absorbed
+    }
+}
+
+pub struct ShieldSystem;
+impl<'a> System<'a> for ShieldSystem {
+    type SystemData = (Read<'a, DeltaTime>, WriteStorage<'a, Shield>);
+
+    fn run(&mut self, (dt, mut shield): Self::SystemData) {
+        let dt = dt.to_seconds();
+
+        for shield in (&mut shield).join() {
+            if shield.regen_delay > 0.0 {
+                shield.regen_delay -= dt;
+            } else {
+                shield.current = (shield.current + *SHIELD_REGEN * dt).min(shield.max);
+            }
+        }
+    }
+}
+
+/// The player and the object it crashed into or got hit by when a collision counts as a crash.
+///
+/// Objects pass through the player while it's invulnerable or the game isn't being played.
+pub fn crash<D>(
+    event: &CollisionEvent,
+    state: GameState,
+    player: &ReadStorage<Player>,
+    invulnerable: &Storage<Invulnerable, D>,
+) -> Option<(Entity, Entity)>
+where
+    D: Deref<Target = MaskedStorage<Invulnerable>>,
+{
+    // The player can be either side of the collision
+    let (player_entity, other) = if player.contains(event.entity) {
+        (event.entity, event.other)
+    } else if player.contains(event.other) {
+        (event.other, event.entity)
+    } else {
+        return None;
+    };
+
+    if invulnerable.contains(player_entity) || state != GameState::Playing {
+        None
+    } else {
+        Some((player_entity, other))
+    }
+}
+
+/// Damage the player when it crashes into an asteroid or an enemy rocket or gets shot by an enemy.
+///
+/// Crashes do more damage the faster they are, the shield absorbs the damage first. When the
+/// health runs out the player loses a life, the game is over when there are no lives left. What
+/// the player crashed into is destroyed by the asteroid and rocket hit systems, which run first.
+#[derive(Default)]
+pub struct PlayerHitSystem {
+    reader: Option<ReaderId<CollisionEvent>>,
+}
+impl<'a> System<'a> for PlayerHitSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        Read<'a, LazyUpdate>,
+        Write<'a, Lives>,
+        Write<'a, GameState>,
+        Write<'a, WorldRng>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Asteroid>,
+        ReadStorage<'a, Rocket>,
+        ReadStorage<'a, Laser>,
+        ReadStorage<'a, Projectile>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Collider>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Shield>,
+        WriteStorage<'a, Invulnerable>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            events,
+            updater,
+            mut lives,
+            mut state,
+            mut rng,
+            player,
+            asteroid,
+            rocket,
+            laser,
+            projectile,
+            pos,
+            vel,
+            collider,
+            mut health,
+            mut shield,
+            mut invulnerable,
+        ): Self::SystemData,
+    ) {
+        // Blow up an object where it currently is
+        let mut explode = |entity: Entity, amount: usize, color: u32| {
+            if let (Some(pos), Some(vel)) = (pos.get(entity), vel.get(entity)) {
+                let center = match collider.get(entity) {
+                    Some(collider) => pos.0 + collider.offset,
+                    None => pos.0,
+                };
+
+                particle::explosion(&entities, &updater, &mut *rng, center, vel.0, amount, color);
+            }
+        };
+
+        for event in events.read(self.reader.as_mut().unwrap()) {
+            let (player_entity, other) = match crash(event, *state, &player, &invulnerable) {
+                Some(crash) => crash,
+                None => continue,
+            };
+
+            let damage = match damage_source(other, &laser, &projectile) {
+                Some((_, damage)) => damage,
+                None if asteroid.contains(other) || rocket.contains(other) => {
+                    let speed = match (vel.get(player_entity), vel.get(other)) {
+                        (Some(player_vel), Some(other_vel)) => {
+                            (player_vel.0 - other_vel.0).magnitude()
+                        }
+                        _ => 0.0,
+                    };
+
+                    (speed * *COLLISION_DAMAGE).max(*MIN_COLLISION_DAMAGE)
+                }
+                None => continue,
+            };
+
+            let damage = match shield.get_mut(player_entity) {
+                Some(shield) => shield.absorb(damage),
+                None => damage,
+            };
+            let health = match health.get_mut(player_entity) {
+                Some(health) => health,
+                None => continue,
+            };
+            health.current -= damage;
+
+            let invulnerable_time = if health.current > 0.0 {
+                *HIT_INVULNERABLE_TIME
+            } else {
+                // Lose a life
+                lives.0 = lives.0.saturating_sub(1);
+                explode(player_entity, 32, ROCKET_EXPLOSION_COLOR);
+                if lives.0 == 0 {
+                    let _ = entities.delete(player_entity);
+                    *state = GameState::GameOver;
+                    continue;
+                }
+
+                // Start the next life with everything restored
+                health.current = health.max;
+                if let Some(shield) = shield.get_mut(player_entity) {
+                    *shield = Shield::new(shield.max);
+                }
+
+                *INVULNERABLE_TIME
+            };
+
+            if let Err(err) = invulnerable.insert(player_entity, Invulnerable(invulnerable_time)) {
+                eprintln!("could not make the player invulnerable: {}", err);
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use crate::input::Action;
+use anyhow::{anyhow, Result};
+use gilrs::{Axis, Button, EventType, Gilrs};
+
+/// Stick and trigger values closer to the center than this are ignored.
+#[const_tweaker::tweak(min = 0.0, max = 0.9, step = 0.01)]
+const DEADZONE: f64 = 0.15;
+
+/// Reads the game controllers and turns their input into actions.
+///
+/// The left stick rotates the rocket, the right trigger thrusts and the left trigger brakes, the
+/// amount depends on how far they are pushed.
+pub struct Gamepads {
+    gilrs: Gilrs,
+    /// How far the sticks and triggers push every action.
+    analog: [f64; Action::ALL.len()],
+    /// Whether the buttons for every action are held, the D-pad overlaps with the sticks.
+    buttons: [f64; Action::ALL.len()],
+}
+
+impl Gamepads {
+    /// Connect to the game controllers.
+    pub fn new() -> Result<Self> {
+        let gilrs = Gilrs::new()
+            .map_err(|err| anyhow!("could not initialize the game controllers: {}", err))?;
+
+        Ok(Self {
+            gilrs,
+            analog: [0.0; Action::ALL.len()],
+            buttons: [0.0; Action::ALL.len()],
+        })
+    }
+
+    /// Get all the actions that changed since the previous poll with their new values, between 0
+    /// and 1.
+    pub fn poll(&mut self) -> Vec<(Action, f64)> {
+        let mut changes = vec![];
+
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::AxisChanged(Axis::LeftStickX, value, _) => {
+                    // Split the axis into both directions
+                    let value = deadzone(value as f64);
+                    self.analog[Action::RotateLeft as usize] = (-value).max(0.0);
+                    self.analog[Action::RotateRight as usize] = value.max(0.0);
+                    changes.extend(&[Action::RotateLeft, Action::RotateRight]);
+                }
+                EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+                    self.analog[Action::Thrust as usize] = deadzone(value as f64);
+                    changes.push(Action::Thrust);
+                }
+                EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+                    self.analog[Action::Brake as usize] = deadzone(value as f64);
+                    changes.push(Action::Brake);
+                }
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(action) = button_action(button) {
+                        self.buttons[action as usize] = 1.0;
+                        changes.push(action);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    if let Some(action) = button_action(button) {
+                        self.buttons[action as usize] = 0.0;
+                        changes.push(action);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        // A stick returning to the center doesn't cancel a D-pad button that's still held
+        changes
+            .into_iter()
+            .map(|action| {
+                let value = self.analog[action as usize].max(self.buttons[action as usize]);
+
+                (action, value)
+            })
+            .collect()
+    }
+}
+
+/// The action bound to a digital button.
+fn button_action(button: Button) -> Option<Action> {
+    match button {
+        Button::South => Some(Action::Fire),
+        Button::West => Some(Action::ToggleAutofire),
+        Button::Start => Some(Action::Pause),
+        Button::DPadUp => Some(Action::Thrust),
+        Button::DPadDown => Some(Action::Brake),
+        Button::DPadLeft => Some(Action::RotateLeft),
+        Button::DPadRight => Some(Action::RotateRight),
+        _ => None,
+    }
+}
+
+/// Ignore small values and scale the rest so the output still ranges from 0 to 1.
+fn deadzone(value: f64) -> f64 {
+    if value.abs() < *DEADZONE {
+        0.0
+    } else {
+        value.signum() * (value.abs() - *DEADZONE) / (1.0 - *DEADZONE)
+    }
+}
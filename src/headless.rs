@@ -0,0 +1,208 @@
+use crate::{
+    game::{Lives, Score},
+    input::{Action, InputState},
+    replay::Recording,
+    wave::CurrentWave,
+    Config,
+};
+use anyhow::{anyhow, bail, Result};
+use png::{BitDepth, ColorType, Encoder};
+use safe_transmute::to_bytes;
+use specs::prelude::*;
+use specs_blit::PixelBuffer;
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
+
+/// The outcome of a headless run or replay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    /// The amount of physics steps that were run.
+    pub frames: usize,
+    pub score: u64,
+    pub lives: u32,
+    pub wave: usize,
+    /// Whether the final state matches the recording, `None` when it wasn't a replay or the
+    /// recording has no final state to compare with.
+    pub matches: Option<bool>,
+}
+
+impl Summary {
+    /// Take the score, lives and wave from the world after the final step.
+    fn new(world: &World, frames: usize) -> Self {
+        Self {
+            frames,
+            score: world.read_resource::<Score>().0,
+            lives: world.read_resource::<Lives>().0,
+            wave: world.read_resource::<CurrentWave>().0,
+            matches: None,
+        }
+    }
+}
+
+/// A single step of a scripted input sequence.
+#[derive(Debug, Default)]
+struct Step {
+    /// For how many frames the input state is held.
+    frames: usize,
+    /// Which keys are held.
+    input: InputState,
+}
+
+/// Parse a script where every line contains the amount of frames followed by the keys held.
+///
+/// ```text
+/// # Boost forward for a second while turning left, then shoot
+/// 60 up left
+/// 10 fire
+/// 120
+/// ```
+fn parse_script(script: &str) -> Result<Vec<Step>> {
+    script
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line_number, line)| {
+            let mut words = line.split_whitespace();
+
+            let frames = words
+                .next()
+                .unwrap()
+                .parse()
+                .map_err(|err| anyhow!("line {}: invalid frame count: {}", line_number, err))?;
+
+            let mut step = Step {
+                frames,
+                ..Default::default()
+            };
+            for word in words {
+                match word {
+                    "up" => step.input.set_state(Action::Thrust, true),
+                    "down" => step.input.set_state(Action::Brake, true),
+                    "left" => step.input.set_state(Action::RotateLeft, true),
+                    "right" => step.input.set_state(Action::RotateRight, true),
+                    "fire" => step.input.set_state(Action::Fire, true),
+                    "autofire" => step.input.set_autofire_state(true),
+                    "1" => step.input.set_state(Action::SelectLaser, true),
+                    "2" => step.input.set_state(Action::SelectBullets, true),
+                    "3" => step.input.set_state(Action::SelectMissiles, true),
+                    "4" => step.input.set_state(Action::SelectBeam, true),
+                    "5" => step.input.set_state(Action::SelectMines, true),
+                    key => bail!("line {}: unknown key \"{}\"", line_number, key),
+                }
+            }
+
+            Ok(step)
+        })
+        .collect()
+}
+
+/// Write the pixel buffer as it would be shown in the window to a PNG file.
+fn write_png(buffer: &PixelBuffer, path: &Path) -> Result<()> {
+    let mut encoder = Encoder::new(
+        BufWriter::new(File::create(path)?),
+        buffer.width() as u32,
+        buffer.height() as u32,
+    );
+    encoder.set_color(ColorType::RGB);
+    encoder.set_depth(BitDepth::Eight);
+
+    // The window uses the bytes of the pixels directly, the alpha channel is ignored there
+    let rgb = to_bytes::transmute_to_bytes(buffer.pixels())
+        .chunks(4)
+        .flat_map(|pixel| pixel[..3].iter().copied())
+        .collect::<Vec<_>>();
+
+    encoder.write_header()?.write_image_data(&rgb)?;
+
+    Ok(())
+}
+
+/// Run the game for a set amount of physics steps without a window, GPU or audio device and save the
+/// final frame as a PNG.
+///
+/// When a script is passed the input is read from it, after the script has finished no keys are
+/// held anymore. When a record path is passed the session is saved there so it can be replayed.
+pub fn run(
+    config: &Config,
+    frames: usize,
+    script: Option<&Path>,
+    output: &Path,
+    record: Option<&Path>,
+) -> Result<Summary> {
+    let steps = match script {
+        Some(path) => parse_script(&fs::read_to_string(path)?)?,
+        None => vec![],
+    };
+
+    // Expand the steps into the input for every frame
+    let inputs = steps
+        .into_iter()
+        .flat_map(|step| (0..step.frames).map(move |_| step.input.clone()))
+        .chain(std::iter::repeat_with(InputState::new))
+        .take(frames);
+
+    let mut recording = Recording::new(config);
+    let world = simulate(
+        config,
+        inputs.inspect(|input| recording.record(input)),
+        output,
+    )?;
+
+    if let Some(path) = record {
+        recording.finish(&world);
+        recording.save(path)?;
+    }
+
+    Ok(Summary::new(&world, frames))
+}
+
+/// Play back a recorded session without a window and save the final frame as a PNG.
+///
+/// Fails when the world doesn't end up in the same state as when it was recorded.
+pub fn replay(path: &Path, output: &Path) -> Result<Summary> {
+    let recording = Recording::load(path)?;
+
+    let world = simulate(&recording.config, recording.inputs(), output)?;
+
+    let matches = recording.verify(&world);
+    if matches == Some(false) {
+        bail!("the replay diverged from the recording");
+    }
+
+    Ok(Summary {
+        matches,
+        ..Summary::new(&world, recording.frames())
+    })
+}
+
+/// Build the world and run a physics step for every input, then draw the final frame to the PNG.
+fn simulate(
+    config: &Config,
+    inputs: impl Iterator<Item = InputState>,
+    output: &Path,
+) -> Result<World> {
+    let (mut world, _player) = crate::build_world(config)?;
+    let mut dispatcher = crate::build_dispatcher();
+    dispatcher.setup(&mut world);
+    let mut idle_dispatcher = crate::build_idle_dispatcher();
+    idle_dispatcher.setup(&mut world);
+    let mut render_dispatcher = crate::build_render_dispatcher();
+    render_dispatcher.setup(&mut world);
+
+    for input in inputs {
+        world.insert(input);
+
+        crate::step(&mut world, &mut dispatcher, &mut idle_dispatcher)?;
+    }
+
+    // Only the final frame needs to be drawn
+    crate::render(&mut world, &mut render_dispatcher, 1.0);
+
+    write_png(&world.read_resource::<PixelBuffer>(), output)?;
+
+    Ok(world)
+}
@@ -0,0 +1,391 @@
+use crate::{
+    asteroid::Asteroid,
+    clock::FrameRate,
+    collision::Collider,
+    game::{GameState, Health, Lives, Score, Shield},
+    physics::{Interpolation, Position, Rotation, Velocity, WorldBounds},
+    powerup::{ActivePowerUps, PowerUp},
+    rocket::Rocket,
+    text::{draw_text, Alignment, Text},
+    user::{self, Camera, Player},
+    wave::CurrentWave,
+    weapon::{Weapon, WeaponKind},
+};
+use specs::{prelude::*, Component, DenseVecStorage};
+use specs_blit::PixelBuffer;
+use std::f64::consts::{FRAC_PI_2, PI};
+
+type Vec2 = vek::Vec2<f64>;
+
+/// How far away in world units objects are still shown on the radar.
+#[const_tweaker::tweak(min = 100.0, max = 3000.0, step = 10.0)]
+const RADAR_RANGE: f64 = 800.0;
+
+/// The radius of the radar on the screen in pixels.
+const RADAR_RADIUS: f64 = 70.0;
+/// Distance between the HUD and the edges of the screen in pixels.
+const MARGIN: f64 = 16.0;
+/// Size of the speed bar in pixels.
+const SPEED_BAR_SIZE: (usize, usize) = (140, 8);
+/// Size of the health and shield bars in pixels.
+const STATUS_BAR_SIZE: (usize, usize) = (140, 6);
+
+// The bytes of the pixels are shown as red, green & blue, so the colors are written as 0xBBGGRR
+const HUD_COLOR: u32 = 0xFF_FF_FF;
+const DIM_COLOR: u32 = 0x44_44_44;
+const ASTEROID_COLOR: u32 = 0xAA_AA_AA;
+const ROCKET_COLOR: u32 = 0x44_44_FF;
+const BOOST_COLOR: u32 = 0x00_AA_FF;
+const HEALTH_COLOR: u32 = 0x44_DD_44;
+const SHIELD_COLOR: u32 = 0xFF_BB_44;
+const POWERUP_COLOR: u32 = 0x44_FF_FF;
+
+/// Which line of the title, pause and game over messages a text shows.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    Title,
+    Subtitle,
+}
+
+/// Spawn the texts showing the messages of the title, pause and game over screens, they are empty
+/// until the `MessageSystem` fills them.
+pub fn spawn_messages(world: &mut World) {
+    let (width, height) = {
+        let buffer = world.read_resource::<PixelBuffer>();
+
+        (buffer.width() as f64, buffer.height() as f64)
+    };
+    let center = Vec2::new(width / 2.0, height / 3.0);
+
+    world
+        .create_entity()
+        .with(Message::Title)
+        .with(
+            Text::new("", center.x, center.y)
+                .with_color(HUD_COLOR)
+                .with_scale(6)
+                .with_alignment(Alignment::Center),
+        )
+        .build();
+    world
+        .create_entity()
+        .with(Message::Subtitle)
+        .with(
+            Text::new("", center.x, center.y + 80.0)
+                .with_color(HUD_COLOR)
+                .with_scale(2)
+                .with_alignment(Alignment::Center),
+        )
+        .build();
+}
+
+/// Show the messages of the title, pause and game over screens, they are empty while playing.
+pub struct MessageSystem;
+impl<'a> System<'a> for MessageSystem {
+    type SystemData = (
+        Read<'a, GameState>,
+        Read<'a, Score>,
+        Read<'a, CurrentWave>,
+        ReadStorage<'a, Message>,
+        WriteStorage<'a, Text>,
+    );
+
+    fn run(&mut self, (state, score, wave, message, mut text): Self::SystemData) {
+        let (title, subtitle) = match *state {
+            GameState::Title => ("ROCKET GAME".to_string(), "Press fire to start".to_string()),
+            GameState::GameOver => (
+                "GAME OVER".to_string(),
+                format!(
+                    "Score: {}, wave: {}\nPress fire to play again",
+                    score.0, wave.0
+                ),
+            ),
+            GameState::Paused => ("PAUSED".to_string(), "Press pause to resume".to_string()),
+            GameState::Playing => (String::new(), String::new()),
+        };
+
+        for (message, text) in (&message, &mut text).join() {
+            text.text = match message {
+                Message::Title => title.clone(),
+                Message::Subtitle => subtitle.clone(),
+            };
+        }
+    }
+}
+
+/// Draw the health, shield, active power-ups, weapon, speed, heading, score, lives, wave, frame
+/// rate and a radar on top of everything, the title and game over screens only show their
+/// message.
+///
+/// It must run after the sprites are drawn.
+pub struct HudSystem;
+impl<'a> System<'a> for HudSystem {
+    type SystemData = (
+        Write<'a, PixelBuffer>,
+        Read<'a, Camera>,
+        Read<'a, Interpolation>,
+        Read<'a, WorldBounds>,
+        Read<'a, GameState>,
+        Read<'a, Score>,
+        Read<'a, Lives>,
+        Read<'a, CurrentWave>,
+        Read<'a, FrameRate>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Asteroid>,
+        ReadStorage<'a, Rocket>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, Health>,
+        ReadStorage<'a, Shield>,
+        ReadStorage<'a, ActivePowerUps>,
+        ReadStorage<'a, PowerUp>,
+        ReadStorage<'a, Weapon>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut buffer,
+            camera,
+            alpha,
+            bounds,
+            state,
+            score,
+            lives,
+            wave,
+            frame_rate,
+            player,
+            asteroid,
+            rocket,
+            pos,
+            vel,
+            rot,
+            collider,
+            health,
+            shield,
+            active_power_ups,
+            power_up,
+            weapon,
+        ): Self::SystemData,
+    ) {
+        let width = buffer.width() as f64;
+        let height = buffer.height() as f64;
+
+        let camera = camera.interpolate(alpha.0);
+
+        // The frame rate in the top right, it's only measured when there's a window
+        if frame_rate.0 > 0.0 {
+            draw_text(
+                &mut buffer,
+                &format!("FPS {:.0}", frame_rate.0),
+                Vec2::new(width - MARGIN, MARGIN),
+                HUD_COLOR,
+                2,
+                Alignment::Right,
+            );
+        }
+
+        // Title and game over screens only show the message
+        if *state == GameState::Title || *state == GameState::GameOver {
+            return;
+        }
+
+        // Score, lives and the wave in the top left
+        draw_text(
+            &mut buffer,
+            &format!("SCORE {:06}\nLIVES {}\nWAVE  {}", score.0, lives.0, wave.0),
+            Vec2::new(MARGIN, MARGIN),
+            HUD_COLOR,
+            2,
+            Alignment::Left,
+        );
+
+        // Health and shield bars below it
+        for (_, health, shield) in (&player, &health, shield.maybe()).join() {
+            let (bar_width, bar_height) = STATUS_BAR_SIZE;
+
+            let pos = Vec2::new(MARGIN, MARGIN + 56.0);
+            draw_bar(
+                &mut buffer,
+                pos,
+                (bar_width, bar_height),
+                health.current / health.max,
+                HEALTH_COLOR,
+            );
+
+            if let Some(shield) = shield {
+                draw_bar(
+                    &mut buffer,
+                    pos + Vec2::new(0.0, bar_height as f64 + 4.0),
+                    (bar_width, bar_height),
+                    shield.current / shield.max,
+                    SHIELD_COLOR,
+                );
+            }
+        }
+
+        // The active power-ups with the seconds they have left below the bars
+        for (_, active) in (&player, &active_power_ups).join() {
+            let list = active
+                .iter()
+                .map(|(kind, time_left)| format!("{} {:.0}", kind.name(), time_left.ceil()))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            draw_text(
+                &mut buffer,
+                &list,
+                Vec2::new(
+                    MARGIN,
+                    MARGIN + 56.0 + 2.0 * (STATUS_BAR_SIZE.1 as f64 + 4.0) + 4.0,
+                ),
+                POWERUP_COLOR,
+                2,
+                Alignment::Left,
+            );
+        }
+
+        // The selected weapon above the speed, the beam shows how far it's charged
+        for (_, weapon) in (&player, &weapon).join() {
+            let text = match weapon.kind {
+                WeaponKind::Beam => format!(
+                    "WEAPON {} {:3.0}%",
+                    weapon.kind.name(),
+                    weapon.charge * 100.0
+                ),
+                kind => format!("WEAPON {}", kind.name()),
+            };
+
+            draw_text(
+                &mut buffer,
+                &text,
+                Vec2::new(MARGIN, height - MARGIN - SPEED_BAR_SIZE.1 as f64 - 60.0),
+                HUD_COLOR,
+                2,
+                Alignment::Left,
+            );
+        }
+
+        // Speed and heading in the bottom left
+        for (_, vel, rot) in (&player, &vel, &rot).join() {
+            let max_speed = user::max_boost_speed();
+            let speed = vel.magnitude();
+
+            let (_, bar_height) = SPEED_BAR_SIZE;
+            let bar_pos = Vec2::new(MARGIN, height - MARGIN - bar_height as f64);
+            // Going faster than the normal max speed is only possible while boosting
+            let color = if speed > user::max_speed() {
+                BOOST_COLOR
+            } else {
+                HUD_COLOR
+            };
+            draw_bar(
+                &mut buffer,
+                bar_pos,
+                SPEED_BAR_SIZE,
+                speed / max_speed,
+                color,
+            );
+
+            // Show the heading as a compass bearing, up at the start is north
+            let heading = (rot.0 + FRAC_PI_2).to_degrees().rem_euclid(360.0);
+            draw_text(
+                &mut buffer,
+                &format!(
+                    "SPEED {:3.0}/{:.0}\nHEADING {:03.0}",
+                    speed, max_speed, heading
+                ),
+                bar_pos - Vec2::new(0.0, 40.0),
+                HUD_COLOR,
+                2,
+                Alignment::Left,
+            );
+        }
+
+        // The radar in the bottom right, it's rotated like the camera so up is where the player is
+        // heading
+        let radar_center = Vec2::new(
+            width - MARGIN - RADAR_RADIUS,
+            height - MARGIN - RADAR_RADIUS,
+        );
+        draw_circle(&mut buffer, radar_center, RADAR_RADIUS, DIM_COLOR);
+        draw_rect(&mut buffer, radar_center - 1.0, 3, 3, HUD_COLOR);
+
+        let camera_center = camera.map_pos(&Position(camera.center()));
+        for (pos, collider, asteroid, rocket, player, power_up) in (
+            &pos,
+            collider.maybe(),
+            asteroid.maybe(),
+            rocket.maybe(),
+            player.maybe(),
+            power_up.maybe(),
+        )
+            .join()
+        {
+            let color = match (asteroid, rocket, player, power_up) {
+                (_, _, Some(_), _) => continue,
+                (Some(_), _, _, _) => ASTEROID_COLOR,
+                (_, Some(_), _, _) => ROCKET_COLOR,
+                (_, _, _, Some(_)) => POWERUP_COLOR,
+                _ => continue,
+            };
+
+            let center = match collider {
+                Some(collider) => pos.0 + collider.offset,
+                None => pos.0,
+            };
+            let center = bounds.nearest(center, camera.center());
+
+            let offset = camera.map_pos(&Position(center)) - camera_center;
+            let distance = offset.magnitude();
+            if distance > *RADAR_RANGE {
+                continue;
+            }
+
+            let dot = radar_center + offset * (RADAR_RADIUS / *RADAR_RANGE);
+            draw_rect(&mut buffer, dot - 1.0, 2, 2, color);
+        }
+    }
+}
+
+/// Draw a bar filled for the fraction, between 0 and 1.
+fn draw_bar(
+    buffer: &mut PixelBuffer,
+    pos: Vec2,
+    (width, height): (usize, usize),
+    fraction: f64,
+    color: u32,
+) {
+    draw_rect(buffer, pos, width, height, DIM_COLOR);
+
+    let filled = (fraction.clamp(0.0, 1.0) * width as f64) as usize;
+    draw_rect(buffer, pos, filled, height, color);
+}
+
+/// Fill a rectangle, everything outside of the buffer is clipped.
+fn draw_rect(buffer: &mut PixelBuffer, pos: Vec2, width: usize, height: usize, color: u32) {
+    let buffer_width = buffer.width() as i32;
+    let buffer_height = buffer.height() as i32;
+    let pixels = buffer.pixels_mut();
+
+    let (x, y) = (pos.x as i32, pos.y as i32);
+    for py in y.max(0)..(y + height as i32).min(buffer_height) {
+        for px in x.max(0)..(x + width as i32).min(buffer_width) {
+            pixels[px as usize + py as usize * buffer_width as usize] = color;
+        }
+    }
+}
+
+/// Draw the outline of a circle, everything outside of the buffer is clipped.
+fn draw_circle(buffer: &mut PixelBuffer, center: Vec2, radius: f64, color: u32) {
+    // Enough steps to leave no gaps between the pixels
+    let steps = (2.0 * PI * radius).ceil() as usize;
+    for step in 0..steps {
+        let angle = step as f64 / steps as f64 * 2.0 * PI;
+        let point = center + Vec2::new(angle.cos(), angle.sin()) * radius;
+
+        draw_rect(buffer, point, 1, 1, color);
+    }
+}
@@ -0,0 +1,262 @@
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs, path::Path};
+use winit::event::VirtualKeyCode;
+
+/// Something the player can do, keys are bound to actions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Thrust,
+    Brake,
+    RotateLeft,
+    RotateRight,
+    Fire,
+    /// Switch between shooting once per press and shooting while fire is held.
+    ToggleAutofire,
+    /// Switch between shooting where the rocket is heading and shooting at the mouse cursor.
+    ToggleAim,
+    Pause,
+    SelectLaser,
+    SelectBullets,
+    SelectMissiles,
+    SelectBeam,
+    SelectMines,
+}
+
+impl Action {
+    /// All actions.
+    pub const ALL: [Action; 13] = [
+        Action::Thrust,
+        Action::Brake,
+        Action::RotateLeft,
+        Action::RotateRight,
+        Action::Fire,
+        Action::ToggleAutofire,
+        Action::ToggleAim,
+        Action::Pause,
+        Action::SelectLaser,
+        Action::SelectBullets,
+        Action::SelectMissiles,
+        Action::SelectBeam,
+        Action::SelectMines,
+    ];
+}
+
+/// How far every action is pushed, between 0 and 1.
+///
+/// Keys are either fully pushed or not at all, the sticks and triggers of game controllers can be
+/// anywhere in between.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputState {
+    values: [f64; Action::ALL.len()],
+    autofire: bool,
+    aim: bool,
+    /// Position of the mouse cursor on the screen.
+    cursor: [f64; 2],
+}
+
+impl InputState {
+    /// Instantiate a new input state with nothing held.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set whether the action is held.
+    pub fn set_state(&mut self, action: Action, pressed: bool) {
+        self.set_value(action, if pressed { 1.0 } else { 0.0 });
+    }
+
+    /// Get whether the action is held or not.
+    pub fn pressed(&self, action: Action) -> bool {
+        self.value(action) > 0.0
+    }
+
+    /// Set how far the action is pushed, between 0 and 1.
+    pub fn set_value(&mut self, action: Action, value: f64) {
+        self.values[action as usize] = value.clamp(0.0, 1.0);
+    }
+
+    /// Get how far the action is pushed, between 0 and 1.
+    pub fn value(&self, action: Action) -> f64 {
+        self.values[action as usize]
+    }
+
+    /// Set whether the gun keeps shooting while fire is held.
+    pub fn set_autofire_state(&mut self, enabled: bool) {
+        self.autofire = enabled;
+    }
+
+    /// Get whether the gun keeps shooting while fire is held.
+    pub fn autofire_enabled(&self) -> bool {
+        self.autofire
+    }
+
+    /// Set whether the gun shoots at the mouse cursor.
+    pub fn set_aim_state(&mut self, enabled: bool) {
+        self.aim = enabled;
+    }
+
+    /// Get whether the gun shoots at the mouse cursor.
+    pub fn aim_enabled(&self) -> bool {
+        self.aim
+    }
+
+    /// Set the position of the mouse cursor in screen pixels.
+    pub fn set_cursor(&mut self, x: f64, y: f64) {
+        self.cursor = [x, y];
+    }
+
+    /// Get the position of the mouse cursor in screen pixels.
+    pub fn cursor(&self) -> (f64, f64) {
+        (self.cursor[0], self.cursor[1])
+    }
+}
+
+/// Something that pushes actions, every key counts as a separate source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputSource {
+    Key(VirtualKeyCode),
+    Mouse,
+    Gamepad,
+}
+
+/// How far every source pushes the actions, so releasing an action on one source doesn't cancel it
+/// while another source still holds it.
+#[derive(Debug, Default)]
+pub struct InputSources {
+    values: HashMap<(InputSource, Action), f64>,
+}
+
+impl InputSources {
+    /// Instantiate with nothing held.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how far a source pushes the action, returns how far all sources together push it.
+    pub fn set_value(&mut self, source: InputSource, action: Action, value: f64) -> f64 {
+        if value > 0.0 {
+            self.values.insert((source, action), value);
+        } else {
+            self.values.remove(&(source, action));
+        }
+
+        self.value(action)
+    }
+
+    /// Get how far the action is pushed by the source pushing it the furthest.
+    pub fn value(&self, action: Action) -> f64 {
+        self.values
+            .iter()
+            .filter(|((_, held), _)| *held == action)
+            .map(|(_, value)| *value)
+            .fold(0.0, f64::max)
+    }
+}
+
+/// The keys bound to every action, loaded from a TOML file:
+///
+/// ```toml
+/// thrust = ["Comma", "Up"]
+/// brake = ["O", "Down"]
+/// rotate_left = ["A", "Left"]
+/// rotate_right = ["E", "Right"]
+/// ```
+///
+/// Actions that are missing from the file keep their default keys, a key can't be bound to more
+/// than one action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+    pub thrust: Vec<VirtualKeyCode>,
+    pub brake: Vec<VirtualKeyCode>,
+    pub rotate_left: Vec<VirtualKeyCode>,
+    pub rotate_right: Vec<VirtualKeyCode>,
+    pub fire: Vec<VirtualKeyCode>,
+    pub toggle_autofire: Vec<VirtualKeyCode>,
+    pub toggle_aim: Vec<VirtualKeyCode>,
+    pub pause: Vec<VirtualKeyCode>,
+    pub select_laser: Vec<VirtualKeyCode>,
+    pub select_bullets: Vec<VirtualKeyCode>,
+    pub select_missiles: Vec<VirtualKeyCode>,
+    pub select_beam: Vec<VirtualKeyCode>,
+    pub select_mines: Vec<VirtualKeyCode>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use VirtualKeyCode::*;
+
+        Self {
+            thrust: vec![W, Up],
+            brake: vec![S, Down],
+            rotate_left: vec![A, Left],
+            rotate_right: vec![D, Right],
+            fire: vec![Space],
+            toggle_autofire: vec![F],
+            toggle_aim: vec![M],
+            pause: vec![P, Escape],
+            select_laser: vec![Key1, Numpad1],
+            select_bullets: vec![Key2, Numpad2],
+            select_missiles: vec![Key3, Numpad3],
+            select_beam: vec![Key4, Numpad4],
+            select_mines: vec![Key5, Numpad5],
+        }
+    }
+}
+
+impl Bindings {
+    /// Read the bindings from a TOML file.
+    ///
+    /// Every key can only be bound to a single action, including the default keys of the actions
+    /// missing from the file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bindings: Self = toml::from_str(&fs::read_to_string(path)?)?;
+
+        // A key bound twice would only ever trigger the first action
+        let mut bound: Vec<(VirtualKeyCode, Action)> = vec![];
+        for action in Action::ALL.iter() {
+            for key in bindings.keys(*action) {
+                if let Some((_, first)) = bound.iter().find(|(bound_key, _)| bound_key == key) {
+                    bail!(
+                        "{}: key {:?} is bound to both {:?} and {:?}",
+                        path.display(),
+                        key,
+                        first,
+                        action
+                    );
+                }
+                bound.push((*key, *action));
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    /// The keys bound to an action.
+    pub fn keys(&self, action: Action) -> &[VirtualKeyCode] {
+        match action {
+            Action::Thrust => &self.thrust,
+            Action::Brake => &self.brake,
+            Action::RotateLeft => &self.rotate_left,
+            Action::RotateRight => &self.rotate_right,
+            Action::Fire => &self.fire,
+            Action::ToggleAutofire => &self.toggle_autofire,
+            Action::ToggleAim => &self.toggle_aim,
+            Action::Pause => &self.pause,
+            Action::SelectLaser => &self.select_laser,
+            Action::SelectBullets => &self.select_bullets,
+            Action::SelectMissiles => &self.select_missiles,
+            Action::SelectBeam => &self.select_beam,
+            Action::SelectMines => &self.select_mines,
+        }
+    }
+
+    /// The action bound to a key.
+    pub fn action(&self, key: VirtualKeyCode) -> Option<Action> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|action| self.keys(*action).contains(&key))
+    }
+}
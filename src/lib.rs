@@ -0,0 +1,486 @@
+//! A small procedurally generated arcade game.
+//!
+//! The simulation is built as a [specs](https://github.com/amethyst/specs) world and dispatcher,
+//! so different front-ends can run the exact same game:
+//!
+//! ```no_run
+//! use rocket_game::{build_dispatcher, build_render_dispatcher, build_world, render, update, Config};
+//!
+//! # fn main() -> anyhow::Result<()> {
+//! let (mut world, _player) = build_world(&Config::default())?;
+//! let mut dispatcher = build_dispatcher();
+//! dispatcher.setup(&mut world);
+//! let mut render_dispatcher = build_render_dispatcher();
+//! render_dispatcher.setup(&mut world);
+//!
+//! // Run a single physics step and draw the result
+//! update(&mut world, &mut dispatcher);
+//! render(&mut world, &mut render_dispatcher, 1.0);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod ai;
+pub mod asteroid;
+pub mod audio;
+pub mod clock;
+pub mod collision;
+pub mod game;
+pub mod gamepad;
+pub mod headless;
+pub mod hud;
+pub mod input;
+pub mod particle;
+pub mod physics;
+pub mod powerup;
+pub mod projectile;
+pub mod random;
+pub mod replay;
+pub mod rocket;
+pub mod sprite;
+pub mod starfield;
+pub mod text;
+pub mod user;
+pub mod wave;
+pub mod weapon;
+
+use crate::{
+    ai::{AiController, AiSystem},
+    asteroid::*,
+    audio::Audio,
+    clock::PHYSICS_RATE,
+    collision::{Collider, CollisionSystem},
+    game::*,
+    hud::{spawn_messages, HudSystem, Message, MessageSystem},
+    input::{Action, InputState},
+    particle::{Exhaust, ExhaustSystem, Particle, ParticleSystem},
+    physics::*,
+    powerup::{ActivePowerUps, DropChance, PowerUp, PowerUpSprites, PowerUpSystem},
+    projectile::{
+        Laser, LaserGun, LaserGunSystem, LaserLifetimeSystem, Lifetime, LifetimeSystem, Projectile,
+        ProjectileSystem,
+    },
+    random::{Seed, WorldRng},
+    rocket::*,
+    sprite::{Line, LineSystem},
+    starfield::{Starfield, StarfieldSystem},
+    text::{Text, TextSystem},
+    user::*,
+    wave::{CurrentWave, Waves},
+    weapon::{Weapon, WeaponSystem},
+};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_blit::{PixelBuffer, RenderSystem, Sprite};
+
+type Vec2 = vek::Vec2<f64>;
+
+/// The settings used to build the world.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Width of the pixel buffer.
+    pub width: usize,
+    /// Height of the pixel buffer.
+    pub height: usize,
+    /// Width of the world, objects wrap around at the edges.
+    pub world_width: usize,
+    /// Height of the world, objects wrap around at the edges.
+    pub world_height: usize,
+    /// Stream an endless asteroid field in chunks around the camera instead of wrapping the world,
+    /// the world size and amount of asteroids are ignored.
+    pub streamed: bool,
+    /// The seed for everything that's randomly generated, the same seed always results in the
+    /// same world.
+    pub seed: u64,
+    /// The enemies spawned every wave, a new wave starts when all enemies are destroyed.
+    pub waves: Waves,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            width: 1200,
+            height: 800,
+            world_width: 2400,
+            world_height: 1600,
+            streamed: false,
+            seed: 0,
+            waves: Waves::default(),
+        }
+    }
+}
+
+/// Build the ECS world with all components, resources and initial entities.
+///
+/// Returns the world and the player entity.
+pub fn build_world(config: &Config) -> Result<(World, Entity)> {
+    // Setup the ECS system
+    let mut world = World::new();
+
+    // Load the game components
+    world.register::<Position>();
+    world.register::<PreviousPosition>();
+    world.register::<Velocity>();
+    world.register::<Rotation>();
+    world.register::<AngularVelocity>();
+    world.register::<Drag>();
+    world.register::<RotationFollowsVelocity>();
+    world.register::<Lifetime>();
+    world.register::<Asteroid>();
+    world.register::<Laser>();
+    world.register::<LaserGun>();
+    world.register::<Weapon>();
+    world.register::<Projectile>();
+    world.register::<Rocket>();
+    world.register::<Player>();
+    world.register::<MovesWithCamera>();
+    world.register::<RotatesWithCamera>();
+    world.register::<Line>();
+    world.register::<Collider>();
+    world.register::<Invulnerable>();
+    world.register::<Health>();
+    world.register::<Shield>();
+    world.register::<AiController>();
+    world.register::<Text>();
+    world.register::<Message>();
+    world.register::<Particle>();
+    world.register::<Exhaust>();
+    world.register::<PowerUp>();
+    world.register::<ActivePowerUps>();
+
+    // Load the sprite rendering component
+    world.register::<Sprite>();
+
+    // Add the pixel buffer as a resource so it can be accessed from the RenderSystem later, to be
+    // updated every frame
+    world.insert(PixelBuffer::new(config.width, config.height));
+
+    // Add the deltatime to calculate the physics, the physics always run at a fixed rate
+    world.insert(DeltaTime::new(1.0 / PHYSICS_RATE));
+
+    // Add the interpolation between physics steps, to be updated every rendered frame
+    world.insert(Interpolation(1.0));
+
+    // Add the current keyboard state, to be updated every frame
+    world.insert(InputState::new());
+
+    // Start playing right away, the window shows the title screen first
+    world.insert(GameState::Playing);
+    world.insert(PreviousInput::default());
+    world.insert(Score::default());
+    world.insert(Lives::default());
+    world.insert(CurrentWave::default());
+
+    // Keep the settings around so a new game can be spawned after a game over
+    world.insert(config.clone());
+
+    // Add the random generator, every procedural generation path uses it
+    let seed = Seed(config.seed);
+    world.insert(seed);
+    world.insert(WorldRng::new(seed));
+
+    // Generate the asteroid and power-up sprites once, sprites are never freed so they are reused
+    let sprites = AsteroidSprites::new(&mut world.write_resource::<WorldRng>())?;
+    world.insert(sprites);
+    let sprites = PowerUpSprites::new(&mut *world.write_resource::<WorldRng>())?;
+    world.insert(sprites);
+
+    if config.streamed {
+        // Add the endless asteroid field, the world doesn't wrap so no bounds are needed
+        world.insert(AsteroidField::new(seed));
+        world.insert(WorldBounds::default());
+    } else {
+        // Add the size of the world, everything wraps around at the edges
+        world.insert(WorldBounds::new(
+            config.world_width as f64,
+            config.world_height as f64,
+        ));
+    }
+
+    // Add the background stars, they don't use the world random generator because they don't
+    // affect the game
+    world.insert(Starfield::new(seed));
+
+    // Add the camera
+    world.insert(Camera::new(Vec2::new(
+        config.width as f64 / 2.0,
+        config.height as f64 / 2.0,
+    )));
+
+    // Add the audio system
+    world.insert(Audio::new());
+
+    let player = spawn_entities(&mut world, config)?;
+
+    Ok((world, player))
+}
+
+/// Spawn the first wave, the screen messages and the player for a new game.
+///
+/// Returns the player entity.
+fn spawn_entities(world: &mut World, config: &Config) -> Result<Entity> {
+    let center = Vec2::new(
+        config.world_width as f64 / 2.0,
+        config.world_height as f64 / 2.0,
+    );
+    spawn_wave(world, config, 1, center)?;
+    spawn_messages(world);
+
+    spawn_rocket(world, config.world_width / 2, config.world_height / 2)
+}
+
+/// Spawn the enemies of a wave in an area of the world size around the center.
+fn spawn_wave(world: &mut World, config: &Config, number: usize, center: Vec2) -> Result<()> {
+    let wave = config.waves.get(number);
+
+    // The streamed asteroid field spawns its own asteroids around the camera
+    if !config.streamed {
+        spawn_asteroids(
+            world,
+            wave.asteroids,
+            wave.asteroid_speed,
+            config.world_width,
+            config.world_height,
+        )?;
+    }
+
+    let size = Vec2::new(config.world_width as f64, config.world_height as f64);
+    spawn_small_rockets(
+        world,
+        wave.rockets,
+        center - size / 2.0,
+        config.world_width,
+        config.world_height,
+    )?;
+
+    world.insert(CurrentWave(number));
+    world.insert(DropChance(wave.powerup_chance));
+
+    Ok(())
+}
+
+/// Start the next wave when all enemies of the current wave are destroyed.
+fn next_wave(world: &mut World) -> Result<()> {
+    let config = (*world.read_resource::<Config>()).clone();
+    if !wave::cleared(world, config.streamed) {
+        return Ok(());
+    }
+
+    // The wrapped world is the same everywhere, the streamed field spawns around the camera
+    let center = if config.streamed {
+        world.read_resource::<Camera>().center()
+    } else {
+        Vec2::new(
+            config.world_width as f64 / 2.0,
+            config.world_height as f64 / 2.0,
+        )
+    };
+
+    let number = world.read_resource::<CurrentWave>().0 + 1;
+    spawn_wave(world, &config, number, center)?;
+
+    // Don't get hit by the enemies spawned on top of the player
+    let entities = world.entities();
+    let player = world.read_storage::<Player>();
+    let mut invulnerable = world.write_storage::<Invulnerable>();
+    for (entity, _) in (&entities, &player).join() {
+        invulnerable.insert(entity, Invulnerable::new())?;
+    }
+
+    Ok(())
+}
+
+/// Remove everything from the world and start a new game with a fresh score and lives.
+///
+/// The random generator isn't reset, so the new game is different from the previous one.
+///
+/// Returns the new player entity.
+pub fn restart(world: &mut World) -> Result<Entity> {
+    world.delete_all();
+    world.maintain();
+
+    // The asteroids of the streamed field are gone, so the chunks must be spawned again
+    if let Some(mut field) = world.try_fetch_mut::<AsteroidField>() {
+        field.reset();
+    }
+
+    world.insert(Score::default());
+    world.insert(Lives::default());
+
+    let config = (*world.read_resource::<Config>()).clone();
+    let player = spawn_entities(world, &config)?;
+
+    world.insert(GameState::Playing);
+
+    Ok(player)
+}
+
+/// Build the dispatcher with all the systems that run every physics step.
+///
+/// `Dispatcher::setup` must be called with the world before the first dispatch, this adds the
+/// resources the systems need, like the collision event channel.
+pub fn build_dispatcher<'a, 'b>() -> Dispatcher<'a, 'b> {
+    DispatcherBuilder::new()
+        .with(PreviousPositionSystem, "previous_position", &[])
+        .with(LifetimeSystem, "lifetime", &[])
+        .with(LaserLifetimeSystem, "laser_lifetime", &[])
+        .with(PlayerControlSystem, "player_control", &[])
+        .with(AiSystem, "ai", &[])
+        .with(DragSystem, "drag", &["player_control"])
+        .with(
+            VelocitySystem,
+            "velocity",
+            &["previous_position", "drag", "ai"],
+        )
+        .with(AngularVelocitySystem, "angular_velocity", &["drag"])
+        .with(WrapSystem, "wrap", &["velocity"])
+        .with(RotationSystem, "rotation", &["velocity"])
+        .with(
+            CameraFollowSystem,
+            "camera_follow",
+            &["wrap", "angular_velocity"],
+        )
+        .with(
+            LaserGunSystem,
+            "laser_gun",
+            &["player_control", "ai", "wrap", "angular_velocity"],
+        )
+        .with(
+            WeaponSystem,
+            "weapon",
+            &["player_control", "wrap", "angular_velocity"],
+        )
+        .with(AsteroidFieldSystem, "asteroid_field", &["camera_follow"])
+        .with(
+            ExhaustSystem,
+            "exhaust",
+            &["player_control", "wrap", "angular_velocity"],
+        )
+        .with(ProjectileSystem, "projectile", &["wrap"])
+        .with(CollisionSystem, "collision", &["wrap", "projectile"])
+        .with(
+            AsteroidSplitSystem::default(),
+            "asteroid_split",
+            &["collision"],
+        )
+        .with(RocketHitSystem::default(), "rocket_hit", &["collision"])
+        .with(
+            PlayerHitSystem::default(),
+            "player_hit",
+            &["collision", "asteroid_split", "rocket_hit"],
+        )
+        .with(PowerUpSystem::default(), "power_up", &["collision"])
+        .with(InvulnerableSystem, "invulnerable", &["player_hit"])
+        .with(ShieldSystem, "shield", &["player_hit"])
+        .build()
+}
+
+/// Build the dispatcher with the systems that keep the world drifting on the title and game over
+/// screens, nothing can be controlled or destroyed.
+pub fn build_idle_dispatcher<'a, 'b>() -> Dispatcher<'a, 'b> {
+    DispatcherBuilder::new()
+        .with(PreviousPositionSystem, "previous_position", &[])
+        .with(LifetimeSystem, "lifetime", &[])
+        .with(LaserLifetimeSystem, "laser_lifetime", &[])
+        .with(DragSystem, "drag", &[])
+        .with(VelocitySystem, "velocity", &["previous_position", "drag"])
+        .with(AngularVelocitySystem, "angular_velocity", &["drag"])
+        .with(WrapSystem, "wrap", &["velocity"])
+        .with(RotationSystem, "rotation", &["velocity"])
+        .with(
+            CameraFollowSystem,
+            "camera_follow",
+            &["wrap", "angular_velocity"],
+        )
+        .with(AsteroidFieldSystem, "asteroid_field", &["camera_follow"])
+        .build()
+}
+
+/// Build the dispatcher with all the systems that draw the world to the pixel buffer.
+pub fn build_render_dispatcher<'a, 'b>() -> Dispatcher<'a, 'b> {
+    // Setup the dispatcher with the blit system
+    DispatcherBuilder::new()
+        // The stars are drawn first as the background
+        .with(StarfieldSystem, "starfield", &[])
+        .with(LineSystem, "line", &["starfield"])
+        // Particles are drawn below the sprites
+        .with(ParticleSystem, "particle", &["starfield"])
+        .with(SpriteRotationSystem, "sprite_rotation", &[])
+        .with(SpritePositionSystem, "sprite_position", &[])
+        .with(FlashSystem, "flash", &["sprite_position"])
+        .with(MessageSystem, "message", &[])
+        .with_thread_local(RenderSystem)
+        // The HUD is drawn on top of the sprites
+        .with_thread_local(HudSystem)
+        // The messages are drawn on top of the HUD
+        .with_thread_local(TextSystem)
+        .build()
+}
+
+/// Run a single fixed physics step of the game.
+pub fn update(world: &mut World, dispatcher: &mut Dispatcher) {
+    // Keep the camera state so it can be interpolated, it will follow the player in the dispatch
+    world.write_resource::<Camera>().store_previous();
+
+    // Update specs
+    dispatcher.dispatch(world);
+
+    // Add/remove entities added in dispatch through `LazyUpdate`
+    world.maintain();
+}
+
+/// Run a single fixed physics step with the systems of the current game state, nothing runs while
+/// the game is paused.
+///
+/// The state only changes through the input, so a recorded session plays back the same way.
+pub fn step(
+    world: &mut World,
+    dispatcher: &mut Dispatcher,
+    idle_dispatcher: &mut Dispatcher,
+) -> Result<()> {
+    let input = (*world.read_resource::<InputState>()).clone();
+    let previous = std::mem::replace(
+        &mut world.write_resource::<PreviousInput>().0,
+        input.clone(),
+    );
+
+    let state = *world.read_resource::<GameState>();
+    if state == GameState::GameOver
+        && input.pressed(Action::Fire)
+        && !previous.pressed(Action::Fire)
+    {
+        // Start a new game
+        restart(world)?;
+    } else {
+        world.insert(state.next(&input, &previous));
+    }
+
+    let state = *world.read_resource::<GameState>();
+    match state {
+        GameState::Playing => {
+            update(world, dispatcher);
+            next_wave(world)?;
+        }
+        GameState::Title | GameState::GameOver => update(world, idle_dispatcher),
+        GameState::Paused => (),
+    }
+
+    Ok(())
+}
+
+/// Draw the world to the pixel buffer.
+///
+/// The alpha is how far the frame is between the previous and the current physics step, between
+/// 0 and 1.
+pub fn render(world: &mut World, dispatcher: &mut Dispatcher, alpha: f64) {
+    {
+        // Clear the buffer
+        let mut buffer = world.write_resource::<PixelBuffer>();
+        buffer.clear(0);
+    }
+
+    world.insert(Interpolation(alpha));
+
+    dispatcher.dispatch(world);
+}
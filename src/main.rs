@@ -1,116 +1,144 @@
-mod asteroid;
-mod audio;
-mod physics;
-mod projectile;
-mod rocket;
-mod sprite;
-mod user;
-
-use crate::{
-    asteroid::*,
+use anyhow::{bail, Result};
+use pixels::{wgpu::Surface, Pixels, SurfaceTexture};
+use rocket_game::{
     audio::Audio,
-    physics::*,
-    projectile::{Laser, LaserLifetimeSystem, Lifetime, LifetimeSystem},
-    rocket::*,
-    sprite::{Line, LineSystem},
-    user::*,
+    clock::{FrameClock, FrameRate},
+    game::{GameState, Score},
+    gamepad::Gamepads,
+    headless::{self, Summary},
+    input::{Action, Bindings, InputSource, InputSources, InputState},
+    replay::Recording,
+    wave::Waves,
+    Config,
 };
-use anyhow::Result;
-use pixels::{wgpu::Surface, Pixels, SurfaceTexture};
 use safe_transmute::to_bytes;
 use specs::prelude::*;
-use specs_blit::{PixelBuffer, RenderSystem, Sprite};
+use specs_blit::PixelBuffer;
+use std::path::PathBuf;
 use winit::{
     dpi::LogicalSize,
-    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, MouseButton, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
 
-type Vec2 = vek::Vec2<f64>;
+fn main() -> Result<()> {
+    let mut args = pico_args::Arguments::from_env();
 
-const WIDTH: usize = 1200;
-const HEIGHT: usize = 800;
+    // Load the waves, falling back to the defaults when there's no file
+    let waves_path = args
+        .opt_value_from_str("--waves")?
+        .unwrap_or_else(|| PathBuf::from("waves.toml"));
+    let waves = if waves_path.exists() {
+        Waves::load(&waves_path)?
+    } else {
+        Waves::default()
+    };
 
-fn main() -> Result<()> {
-    // Setup the ECS system
-    let mut world = World::new();
-
-    // Load the game components
-    world.register::<Position>();
-    world.register::<Velocity>();
-    world.register::<Rotation>();
-    world.register::<CartesianVelocity>();
-    world.register::<RotationFollowsVelocity>();
-    world.register::<Lifetime>();
-    world.register::<Asteroid>();
-    world.register::<Laser>();
-    world.register::<Rocket>();
-    world.register::<MovesWithCamera>();
-    world.register::<RotatesWithCamera>();
-    world.register::<Line>();
-
-    // Load the sprite rendering component
-    world.register::<Sprite>();
-
-    // Add the pixel buffer as a resource so it can be accessed from the RenderSystem later, to be
-    // updated every frame
-    world.insert(PixelBuffer::new(WIDTH, HEIGHT));
-
-    // Add the deltatime to calculate the physics, to be updated every frame
-    world.insert(DeltaTime::new(1.0 / 60.0));
-
-    // Add the current keyboard state, to be updated every frame
-    world.insert(InputState::new());
-
-    // Add the camera
-    world.insert(Camera::new(Vec2::new(
-        WIDTH as f64 / 2.0,
-        HEIGHT as f64 / 2.0,
-    )));
-
-    // Add the audio system
-    world.insert(Audio::new());
-
-    // Spawn the initial asteroids
-    spawn_asteroids(&mut world, 20, WIDTH, HEIGHT)?;
-
-    // Spawn the initial rockets
-    spawn_small_rockets(&mut world, 20, WIDTH, HEIGHT)?;
-
-    // Spawn the player rocket
-    spawn_rocket(&mut world, WIDTH / 2, HEIGHT / 2)?;
-
-    // Setup the dispatcher with the blit system
-    let mut dispatcher = DispatcherBuilder::new()
-        .with(LifetimeSystem, "lifetime", &[])
-        .with(LaserLifetimeSystem, "laser_lifetime", &[])
-        .with(LineSystem, "line", &["laser_lifetime"])
-        .with(CartesianVelocitySystem, "cartesian_velocity", &[])
-        .with(VelocitySystem, "velocity", &[])
-        .with(RotationSystem, "rotation", &["velocity"])
-        .with(
-            CartesianRotationSystem,
-            "cartesian_rotation",
-            &["cartesian_velocity"],
-        )
-        .with(
-            SpriteRotationSystem,
-            "sprite_rotation",
-            &["rotation", "cartesian_rotation"],
-        )
-        .with(
-            SpritePositionSystem,
-            "sprite_position",
-            &["velocity", "cartesian_velocity"],
-        )
-        .with_thread_local(RenderSystem)
-        .build();
+    let config = Config {
+        streamed: args.contains("--streamed"),
+        // Pick a random seed when none is given, it's printed so the run can be reproduced
+        seed: args
+            .opt_value_from_str("--seed")?
+            .unwrap_or_else(rand::random),
+        waves,
+        ..Config::default()
+    };
+    // Load the key bindings, falling back to the defaults when there's no file
+    let bindings_path = args
+        .opt_value_from_str("--bindings")?
+        .unwrap_or_else(|| PathBuf::from("bindings.toml"));
+    let bindings = if bindings_path.exists() {
+        Bindings::load(&bindings_path)?
+    } else {
+        Bindings::default()
+    };
+
+    let record: Option<PathBuf> = args.opt_value_from_str("--record")?;
+    let replay: Option<PathBuf> = args.opt_value_from_str("--replay")?;
+
+    // Run the simulation without a window, GPU or audio device
+    if args.contains("--headless") {
+        let output = args
+            .opt_value_from_str("--output")?
+            .unwrap_or_else(|| PathBuf::from("headless.png"));
+
+        if let Some(replay) = replay {
+            finish_args(args)?;
+
+            let summary = headless::replay(&replay, &output)?;
+            print_summary(&summary);
+            println!(
+                "Replayed {} frames, final frame written to \"{}\"",
+                summary.frames,
+                output.display()
+            );
+            match summary.matches {
+                Some(_) => println!("The final state matches the recording"),
+                None => println!("The recording has no final state to compare with"),
+            }
+
+            return Ok(());
+        }
+
+        let frames = args.opt_value_from_str("--frames")?.unwrap_or(600);
+        let script: Option<PathBuf> = args.opt_value_from_str("--script")?;
+        finish_args(args)?;
+
+        println!("Seed: {}", config.seed);
+
+        let summary = headless::run(
+            &config,
+            frames,
+            script.as_deref(),
+            &output,
+            record.as_deref(),
+        )?;
+        print_summary(&summary);
+        println!(
+            "Simulated {} frames, final frame written to \"{}\"",
+            summary.frames,
+            output.display()
+        );
+
+        return Ok(());
+    }
+
+    finish_args(args)?;
+
+    // Play back a recorded session instead of using the keyboard
+    let (config, mut replay_inputs) = match replay {
+        Some(path) => {
+            let recording = Recording::load(&path)?;
+            let inputs = recording.inputs().collect::<Vec<_>>();
+
+            (recording.config, Some(inputs.into_iter()))
+        }
+        None => (config, None),
+    };
+    println!("Seed: {}", config.seed);
+
+    let mut recording = record.map(|path| (path, Recording::new(&config)));
+
+    let (mut world, _player) = rocket_game::build_world(&config)?;
+    let mut dispatcher = rocket_game::build_dispatcher();
+    let mut idle_dispatcher = rocket_game::build_idle_dispatcher();
+    let mut render_dispatcher = rocket_game::build_render_dispatcher();
+
+    // Add the resources the systems need, like the collision event channel
+    dispatcher.setup(&mut world);
+    idle_dispatcher.setup(&mut world);
+    render_dispatcher.setup(&mut world);
+
+    // Recorded and replayed sessions start playing right away, the title screen isn't part of them
+    if recording.is_none() && replay_inputs.is_none() {
+        world.insert(GameState::Title);
+    }
 
     // Setup the window
     let event_loop = EventLoop::new();
     let window = {
-        let size = LogicalSize::new(WIDTH as f64, HEIGHT as f64);
+        let size = LogicalSize::new(config.width as f64, config.height as f64);
         WindowBuilder::new()
             .with_title("Rocket Game")
             .with_inner_size(size)
@@ -121,8 +149,9 @@ fn main() -> Result<()> {
 
     let mut pixels = {
         let surface = Surface::create(&window);
-        let surface_texture = SurfaceTexture::new(WIDTH as u32, HEIGHT as u32, surface);
-        Pixels::new(WIDTH as u32, HEIGHT as u32, surface_texture)?
+        let surface_texture =
+            SurfaceTexture::new(config.width as u32, config.height as u32, surface);
+        Pixels::new(config.width as u32, config.height as u32, surface_texture)?
     };
 
     {
@@ -131,6 +160,21 @@ fn main() -> Result<()> {
         audio.run();
     }
 
+    // Measure the time between frames to run the physics at a fixed rate
+    let mut clock = FrameClock::new();
+
+    // Keep what every key, the mouse and the game controllers hold apart
+    let mut sources = InputSources::new();
+
+    // Connect the game controllers, the keyboard can still be used when it fails
+    let mut gamepads = match Gamepads::new() {
+        Ok(gamepads) => Some(gamepads),
+        Err(err) => {
+            eprintln!("{}", err);
+            None
+        }
+    };
+
     event_loop.run(move |event, _, control_flow| {
         // ControlFlow::Poll continuously runs the event loop, even if the OS hasn't
         // dispatched any events. This is ideal for games and similar applications.
@@ -142,32 +186,70 @@ fn main() -> Result<()> {
                 ..
             } => {
                 // Close button was pressed
-                *control_flow = ControlFlow::Exit
+                *control_flow = ControlFlow::Exit;
+
+                // Save the recorded session
+                if let Some((path, recording)) = &mut recording {
+                    recording.finish(&world);
+                    match recording.save(path) {
+                        Ok(_) => println!("Recording written to \"{}\"", path.display()),
+                        Err(err) => eprintln!("could not save the recording: {}", err),
+                    }
+                }
             }
             Event::MainEventsCleared => {
                 // Application update code
 
-                {
-                    // Clear the buffer
-                    let mut buffer = world.write_resource::<PixelBuffer>();
-                    buffer.clear(0);
+                // Handle the game controller input
+                if let Some(gamepads) = &mut gamepads {
+                    for (action, value) in gamepads.poll() {
+                        handle_action(
+                            &mut world,
+                            &mut sources,
+                            InputSource::Gamepad,
+                            action,
+                            value,
+                        );
+                    }
                 }
 
-                {
-                    // Update the camera
-                    let mut camera = world.write_resource::<Camera>();
-                    camera.handle_input(
-                        &world.read_resource::<InputState>(),
-                        &mut world.write_resource::<Audio>(),
-                    );
-                    camera.update(world.read_resource::<DeltaTime>().to_seconds());
-                }
+                // Run as many physics steps as fit in the time passed since the previous frame, the
+                // current game state decides which systems run
+                for _ in 0..clock.tick() {
+                    if let Some(inputs) = &mut replay_inputs {
+                        match inputs.next() {
+                            Some(input) => world.insert(input),
+                            None => {
+                                // Give the control back to the player
+                                println!("Replay finished");
+                                world.insert(InputState::new());
+                                replay_inputs = None;
+                            }
+                        }
+                    }
+
+                    if let Some((_, recording)) = &mut recording {
+                        recording.record(&world.read_resource::<InputState>());
+                    }
+
+                    let state = *world.read_resource::<GameState>();
+                    if let Err(err) =
+                        rocket_game::step(&mut world, &mut dispatcher, &mut idle_dispatcher)
+                    {
+                        eprintln!("could not start a new game: {}", err);
+                        *control_flow = ControlFlow::Exit;
+                    }
 
-                // Update specs
-                dispatcher.dispatch(&world);
+                    if state != GameState::GameOver
+                        && *world.read_resource::<GameState>() == GameState::GameOver
+                    {
+                        println!("Game over, score: {}", world.read_resource::<Score>().0);
+                    }
+                }
 
-                // Add/remove entities added in dispatch through `LazyUpdate`
-                world.maintain();
+                // Draw the state between the physics steps
+                *world.write_resource::<FrameRate>() = clock.frame_rate();
+                rocket_game::render(&mut world, &mut render_dispatcher, clock.alpha());
 
                 // Queue a RedrawRequested event.
                 window.request_redraw();
@@ -182,7 +264,10 @@ fn main() -> Result<()> {
                 pixels.get_frame().copy_from_slice(transmuted);
 
                 // Draw the pixels
-                pixels.render();
+                if let Err(err) = pixels.render() {
+                    eprintln!("rendering failed: {}", err);
+                    *control_flow = ControlFlow::Exit;
+                }
             }
             Event::WindowEvent {
                 event: WindowEvent::Resized(new_size),
@@ -191,50 +276,124 @@ fn main() -> Result<()> {
                 pixels.resize(new_size.width, new_size.height);
             }
             Event::WindowEvent {
-                event: WindowEvent::KeyboardInput { input, .. },
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                // Convert the window position to a position in the pixel buffer
+                let (x, y) = pixels
+                    .window_pos_to_pixel((position.x as f32, position.y as f32))
+                    .unwrap_or_else(|pos| pixels.clamp_pixel_pos(pos));
+
+                let mut input_state = world.write_resource::<InputState>();
+                input_state.set_cursor(x as f64, y as f64);
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => {
+                let value = if state == ElementState::Pressed {
+                    1.0
+                } else {
+                    0.0
+                };
+
+                handle_action(
+                    &mut world,
+                    &mut sources,
+                    InputSource::Mouse,
+                    Action::Fire,
+                    value,
+                );
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::KeyboardInput {
+                        input:
+                            KeyboardInput {
+                                virtual_keycode: Some(virtual_code),
+                                state,
+                                ..
+                            },
+                        ..
+                    },
                 ..
             } => {
                 // Handle keyboard input
+                let action = match bindings.action(virtual_code) {
+                    Some(action) => action,
+                    None => return,
+                };
+                let value = if state == ElementState::Pressed {
+                    1.0
+                } else {
+                    0.0
+                };
 
-                // Match WASD & Dvorak (Comma, A, O, E)
-                if let KeyboardInput {
-                    virtual_keycode: Some(virtual_code),
-                    state,
-                    ..
-                } = input
-                {
-                    match virtual_code {
-                        VirtualKeyCode::W | VirtualKeyCode::Comma => {
-                            let mut input_state = world.write_resource::<InputState>();
-                            input_state.set_up_state(state == ElementState::Pressed);
-                        }
-                        VirtualKeyCode::A => {
-                            let mut input_state = world.write_resource::<InputState>();
-                            input_state.set_left_state(state == ElementState::Pressed);
-                        }
-                        VirtualKeyCode::S | VirtualKeyCode::O => {
-                            let mut input_state = world.write_resource::<InputState>();
-                            input_state.set_down_state(state == ElementState::Pressed);
-                        }
-                        VirtualKeyCode::D | VirtualKeyCode::E => {
-                            let mut input_state = world.write_resource::<InputState>();
-                            input_state.set_right_state(state == ElementState::Pressed);
-                        }
-                        VirtualKeyCode::Space => {
-                            if state == ElementState::Pressed {
-                                projectile::shoot_laser(
-                                    &mut world,
-                                    &Vec2::new(WIDTH as f64 / 2.0, HEIGHT as f64 / 2.0),
-                                    90.0,
-                                    500.0,
-                                );
-                            }
-                        }
-                        _ => (),
-                    }
-                }
+                handle_action(
+                    &mut world,
+                    &mut sources,
+                    InputSource::Key(virtual_code),
+                    action,
+                    value,
+                );
             }
             _ => (),
         }
     });
 }
+
+/// Apply a changed action from a key, the mouse or a game controller, the value is how far it's
+/// pushed between 0 and 1.
+///
+/// The action is held as far as the source pushing it the furthest holds it, toggles only flip
+/// when the action goes from released to pressed so key repeats and analog changes are ignored.
+fn handle_action(
+    world: &mut World,
+    sources: &mut InputSources,
+    source: InputSource,
+    action: Action,
+    value: f64,
+) {
+    let was_pressed = sources.value(action) > 0.0;
+    let value = sources.set_value(source, action, value);
+    let just_pressed = value > 0.0 && !was_pressed;
+
+    let mut input_state = world.write_resource::<InputState>();
+    match action {
+        Action::ToggleAutofire if just_pressed => {
+            // Toggle between shooting once per press and shooting while held
+            let enabled = input_state.autofire_enabled();
+            input_state.set_autofire_state(!enabled);
+        }
+        Action::ToggleAim if just_pressed => {
+            // Toggle between shooting where the rocket is heading and at the mouse cursor
+            let enabled = input_state.aim_enabled();
+            input_state.set_aim_state(!enabled);
+        }
+        Action::ToggleAutofire | Action::ToggleAim => (),
+        action => input_state.set_value(action, value),
+    }
+}
+
+/// Print the state the game ended in after a headless run.
+fn print_summary(summary: &Summary) {
+    println!(
+        "Score: {}, lives left: {}, wave: {}",
+        summary.score, summary.lives, summary.wave
+    );
+}
+
+/// Fail on the arguments that haven't been parsed, so a mistyped flag isn't silently ignored.
+fn finish_args(args: pico_args::Arguments) -> Result<()> {
+    let unused = args.finish();
+    if !unused.is_empty() {
+        bail!("unknown arguments: {:?}", unused);
+    }
+
+    Ok(())
+}
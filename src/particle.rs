@@ -0,0 +1,269 @@
+use crate::{
+    collision::Collider,
+    physics::{
+        DeltaTime, Interpolation, Position, PreviousPosition, Rotation, Velocity, WorldBounds,
+    },
+    projectile::Lifetime,
+    random::WorldRng,
+    user::Camera,
+};
+use rand::Rng;
+use specs::{prelude::*, Component, DenseVecStorage};
+use specs_blit::PixelBuffer;
+use std::f64::consts::PI;
+
+type Vec2 = vek::Vec2<f64>;
+
+// The bytes of the pixels are shown as red, green & blue, so the colors are written as 0xBBGGRR
+pub const EXHAUST_COLOR: u32 = 0x00_CC_FF;
+pub const SPARK_COLOR: u32 = 0xAA_FF_FF;
+pub const ASTEROID_EXPLOSION_COLOR: u32 = 0x44_88_AA;
+pub const ROCKET_EXPLOSION_COLOR: u32 = 0x22_66_FF;
+
+/// Particles emitted every second at full thrust.
+#[const_tweaker::tweak(min = 0.0, max = 500.0, step = 1.0)]
+const EXHAUST_RATE: f64 = 120.0;
+#[const_tweaker::tweak(min = 0.0, max = 200.0, step = 1.0)]
+const EXHAUST_SPEED: f64 = 80.0;
+#[const_tweaker::tweak(min = 0.0, max = 2.0, step = 0.01)]
+const EXHAUST_LIFETIME: f64 = 0.4;
+#[const_tweaker::tweak(min = 0.0, max = 200.0, step = 1.0)]
+const EXPLOSION_SPEED: f64 = 50.0;
+#[const_tweaker::tweak(min = 0.0, max = 5.0, step = 0.01)]
+const EXPLOSION_LIFETIME: f64 = 1.0;
+#[const_tweaker::tweak(min = 0.0, max = 200.0, step = 1.0)]
+const SPARK_SPEED: f64 = 80.0;
+#[const_tweaker::tweak(min = 0.0, max = 2.0, step = 0.01)]
+const SPARK_LIFETIME: f64 = 0.25;
+
+/// A single pixel that moves with its velocity and fades to black during its lifetime.
+#[derive(Component, Debug)]
+pub struct Particle {
+    pub color: u32,
+    /// The width and height in pixels.
+    pub size: usize,
+    /// The lifetime it started with, the color fades with the lifetime left.
+    pub lifetime: f64,
+}
+
+/// Emits exhaust particles from the back of a rocket while it's thrusting.
+#[derive(Component, Debug, Default)]
+pub struct Exhaust {
+    /// How far the thrust is pushed, between 0 and 1.
+    pub thrust: f64,
+    /// Particles that should have been emitted but didn't fit in the previous steps.
+    pending: f64,
+}
+
+impl Exhaust {
+    /// Instantiate an exhaust that's always thrusting, used for objects that can't stop.
+    pub fn new(thrust: f64) -> Self {
+        Self {
+            thrust,
+            pending: 0.0,
+        }
+    }
+}
+
+/// Spawn a single particle.
+pub fn emit(
+    entities: &Entities,
+    updater: &LazyUpdate,
+    pos: Vec2,
+    vel: Vec2,
+    color: u32,
+    size: usize,
+    lifetime: f64,
+) {
+    updater
+        .create_entity(entities)
+        .with(Particle {
+            color,
+            size,
+            lifetime,
+        })
+        .with(Position(pos))
+        .with(PreviousPosition(pos))
+        .with(Velocity(vel))
+        .with(Lifetime(lifetime))
+        .build();
+}
+
+/// Spawn particles flying in all directions from the center, the velocity is the velocity of the
+/// destroyed object.
+pub fn explosion<R: Rng>(
+    entities: &Entities,
+    updater: &LazyUpdate,
+    rng: &mut R,
+    center: Vec2,
+    vel: Vec2,
+    amount: usize,
+    color: u32,
+) {
+    for _ in 0..amount {
+        let dir = random_direction(rng);
+        let speed = rng.gen_range(0.2, 1.0) * *EXPLOSION_SPEED;
+        let lifetime = rng.gen_range(0.5, 1.0) * *EXPLOSION_LIFETIME;
+
+        emit(
+            entities,
+            updater,
+            center,
+            vel + dir * speed,
+            color,
+            2,
+            lifetime,
+        );
+    }
+}
+
+/// Spawn a few short lived particles bouncing back from where a laser hit.
+pub fn sparks<R: Rng>(
+    entities: &Entities,
+    updater: &LazyUpdate,
+    rng: &mut R,
+    point: Vec2,
+    laser_dir: Vec2,
+) {
+    for _ in 0..rng.gen_range(4, 8) {
+        // Spray back in the direction the laser came from
+        let dir = (random_direction(rng) - laser_dir).normalized();
+        let speed = rng.gen_range(0.3, 1.0) * *SPARK_SPEED;
+
+        emit(
+            entities,
+            updater,
+            point,
+            dir * speed,
+            SPARK_COLOR,
+            1,
+            *SPARK_LIFETIME,
+        );
+    }
+}
+
+/// A random unit vector.
+fn random_direction<R: Rng>(rng: &mut R) -> Vec2 {
+    let angle = rng.gen_range(-PI, PI);
+
+    Vec2::new(angle.cos(), angle.sin())
+}
+
+pub struct ExhaustSystem;
+impl<'a> System<'a> for ExhaustSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, LazyUpdate>,
+        Write<'a, WorldRng>,
+        WriteStorage<'a, Exhaust>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Collider>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, dt, updater, mut rng, mut exhaust, pos, vel, rot, collider): Self::SystemData,
+    ) {
+        let dt = dt.to_seconds();
+
+        for (entity, exhaust, pos, vel, rot) in (&entities, &mut exhaust, &pos, &vel, &rot).join() {
+            if exhaust.thrust <= 0.0 {
+                exhaust.pending = 0.0;
+                continue;
+            }
+
+            let heading = Vec2::new(rot.cos(), rot.sin());
+            // Emit from the back of the rocket
+            let (center, length) = match collider.get(entity) {
+                Some(collider) => (pos.0 + collider.offset, collider.offset.y),
+                None => (pos.0, 0.0),
+            };
+            let back = center - heading * length;
+
+            exhaust.pending += *EXHAUST_RATE * exhaust.thrust * dt;
+            while exhaust.pending >= 1.0 {
+                exhaust.pending -= 1.0;
+
+                // Spread the particles a bit so it looks like a flame
+                let spread: f64 = rng.gen_range(-0.3, 0.3);
+                let (sin, cos) = spread.sin_cos();
+                let dir = -Vec2::new(
+                    heading.x * cos - heading.y * sin,
+                    heading.x * sin + heading.y * cos,
+                );
+                let speed = rng.gen_range(0.5, 1.0) * *EXHAUST_SPEED;
+                let lifetime = rng.gen_range(0.5, 1.0) * *EXHAUST_LIFETIME;
+
+                emit(
+                    &entities,
+                    &updater,
+                    back,
+                    vel.0 + dir * speed,
+                    EXHAUST_COLOR,
+                    2,
+                    lifetime,
+                );
+            }
+        }
+    }
+}
+
+/// Draw the particles directly into the pixel buffer.
+pub struct ParticleSystem;
+impl<'a> System<'a> for ParticleSystem {
+    type SystemData = (
+        Write<'a, PixelBuffer>,
+        Read<'a, Camera>,
+        Read<'a, Interpolation>,
+        Read<'a, WorldBounds>,
+        ReadStorage<'a, Particle>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, PreviousPosition>,
+        ReadStorage<'a, Lifetime>,
+    );
+
+    fn run(
+        &mut self,
+        (mut buffer, camera, alpha, bounds, particle, pos, prev_pos, lifetime): Self::SystemData,
+    ) {
+        let width = buffer.width() as i32;
+        let height = buffer.height() as i32;
+        let pixels = buffer.pixels_mut();
+
+        let camera = camera.interpolate(alpha.0);
+
+        for (particle, pos, prev_pos, lifetime) in
+            (&particle, &pos, prev_pos.maybe(), &lifetime).join()
+        {
+            // Render the position between the previous and the current physics step
+            let pos = match prev_pos {
+                Some(prev_pos) => Vec2::lerp(prev_pos.0, pos.0, alpha.0),
+                None => pos.0,
+            };
+            let pos = camera.map_pos(&Position(bounds.nearest(pos, camera.center())));
+
+            let color = fade(
+                particle.color,
+                (lifetime.0 / particle.lifetime).clamp(0.0, 1.0),
+            );
+
+            let size = particle.size as i32;
+            let (x, y) = (pos.x as i32 - size / 2, pos.y as i32 - size / 2);
+            for py in y.max(0)..(y + size).min(height) {
+                for px in x.max(0)..(x + size).min(width) {
+                    pixels[px as usize + py as usize * width as usize] = color;
+                }
+            }
+        }
+    }
+}
+
+/// Scale every channel of the color, 1 keeps the color and 0 is black.
+fn fade(color: u32, amount: f64) -> u32 {
+    let channel = |shift: u32| ((((color >> shift) & 0xFF) as f64 * amount) as u32) << shift;
+
+    channel(16) | channel(8) | channel(0)
+}
@@ -1,6 +1,11 @@
-use crate::user::{Camera, MovesWithCamera, RotatesWithCamera};
+use crate::{
+    collision::Collider,
+    user::{Camera, MovesWithCamera, Player, RotatesWithCamera},
+};
 use derive_deref::{Deref, DerefMut};
-use specs::{Component, DenseVecStorage, Join, Read, ReadStorage, System, WriteStorage};
+use specs::{
+    Component, DenseVecStorage, Entities, Join, Read, ReadStorage, System, Write, WriteStorage,
+};
 use specs_blit::Sprite;
 use std::time::Duration;
 
@@ -11,7 +16,7 @@ pub struct DeltaTime(pub Duration);
 
 impl DeltaTime {
     pub fn new(time: f64) -> Self {
-        DeltaTime(Duration::from_millis((time * 1000.0) as u64))
+        DeltaTime(Duration::from_secs_f64(time))
     }
 
     pub fn to_seconds(&self) -> f64 {
@@ -28,6 +33,61 @@ impl Position {
     }
 }
 
+/// The size of the world, positions wrap around at the edges like in the classic Asteroids.
+///
+/// An axis with a size of zero doesn't wrap. The size should be larger than the diagonal of the
+/// screen, otherwise objects near the seam can't be drawn on both sides.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WorldBounds {
+    pub size: Vec2,
+}
+
+impl WorldBounds {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self {
+            size: Vec2::new(width, height),
+        }
+    }
+
+    /// Wrap a position so it falls inside the bounds.
+    pub fn wrap(&self, pos: Vec2) -> Vec2 {
+        Vec2::new(wrap_axis(pos.x, self.size.x), wrap_axis(pos.y, self.size.y))
+    }
+
+    /// Get the copy of a position that is the closest to a reference point, so an object on the
+    /// other side of the seam is found right next to the reference.
+    pub fn nearest(&self, pos: Vec2, reference: Vec2) -> Vec2 {
+        Vec2::new(
+            nearest_axis(pos.x, reference.x, self.size.x),
+            nearest_axis(pos.y, reference.y, self.size.y),
+        )
+    }
+}
+
+fn wrap_axis(value: f64, size: f64) -> f64 {
+    if size > 0.0 {
+        value.rem_euclid(size)
+    } else {
+        value
+    }
+}
+
+fn nearest_axis(value: f64, reference: f64, size: f64) -> f64 {
+    if size > 0.0 {
+        value - ((value - reference) / size).round() * size
+    } else {
+        value
+    }
+}
+
+/// How far the rendering is between the previous and the current physics step, between 0 and 1.
+#[derive(Debug, Default)]
+pub struct Interpolation(pub f64);
+
+/// The position at the previous physics step, used to interpolate the rendering.
+#[derive(Component, Debug, Default, Deref, DerefMut)]
+pub struct PreviousPosition(pub Vec2);
+
 #[derive(Component, Debug, Default, Deref, DerefMut)]
 pub struct Velocity(pub Vec2);
 
@@ -37,18 +97,39 @@ impl Velocity {
     }
 }
 
-#[derive(Component, Debug, Default)]
-pub struct CartesianVelocity {
-    pub rot: f64,
-    pub speed: f64,
-}
-
 #[derive(Component, Debug, Default, Deref, DerefMut)]
 pub struct Rotation(pub f64);
 
+/// Rotation speed in radians per second.
+#[derive(Component, Debug, Default, Deref, DerefMut)]
+pub struct AngularVelocity(pub f64);
+
+/// Slows down the velocity and angular velocity, the values are the fraction lost per second.
+#[derive(Component, Debug, Default)]
+pub struct Drag {
+    pub linear: f64,
+    pub angular: f64,
+}
+
 #[derive(Component, Debug, Default)]
 pub struct RotationFollowsVelocity;
 
+pub struct PreviousPositionSystem;
+impl<'a> System<'a> for PreviousPositionSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, PreviousPosition>,
+    );
+
+    fn run(&mut self, (entities, pos, mut prev_pos): Self::SystemData) {
+        for (entity, pos) in (&entities, &pos).join() {
+            // Overwrites the previous value
+            let _ = prev_pos.insert(entity, PreviousPosition(pos.0));
+        }
+    }
+}
+
 pub struct VelocitySystem;
 impl<'a> System<'a> for VelocitySystem {
     type SystemData = (
@@ -66,44 +147,79 @@ impl<'a> System<'a> for VelocitySystem {
     }
 }
 
-pub struct CartesianVelocitySystem;
-impl<'a> System<'a> for CartesianVelocitySystem {
+/// Move objects leaving the world to the other side.
+pub struct WrapSystem;
+impl<'a> System<'a> for WrapSystem {
     type SystemData = (
-        Read<'a, DeltaTime>,
-        ReadStorage<'a, CartesianVelocity>,
+        Read<'a, WorldBounds>,
+        Write<'a, Camera>,
+        ReadStorage<'a, Player>,
         WriteStorage<'a, Position>,
+        WriteStorage<'a, PreviousPosition>,
     );
 
-    fn run(&mut self, (dt, vel, mut pos): Self::SystemData) {
-        let dt = dt.to_seconds();
+    fn run(&mut self, (bounds, mut camera, player, mut pos, mut prev_pos): Self::SystemData) {
+        for (pos, prev_pos, player) in (&mut pos, (&mut prev_pos).maybe(), player.maybe()).join() {
+            let wrapped = bounds.wrap(pos.0);
+            if wrapped == pos.0 {
+                continue;
+            }
+            let offset = wrapped - pos.0;
+            pos.0 = wrapped;
 
-        for (vel, pos) in (&vel, &mut pos).join() {
-            pos.0.x += vel.rot.sin() * vel.speed * dt;
-            pos.0.y += vel.rot.cos() * vel.speed * dt;
+            // Move the previous positions along so the rendering isn't interpolated across the world
+            if let Some(prev_pos) = prev_pos {
+                prev_pos.0 += offset;
+            }
+            if player.is_some() {
+                camera.translate_previous(offset);
+            }
         }
     }
 }
 
-pub struct RotationSystem;
-impl<'a> System<'a> for RotationSystem {
+pub struct AngularVelocitySystem;
+impl<'a> System<'a> for AngularVelocitySystem {
     type SystemData = (
-        ReadStorage<'a, Velocity>,
+        Read<'a, DeltaTime>,
+        ReadStorage<'a, AngularVelocity>,
         WriteStorage<'a, Rotation>,
-        ReadStorage<'a, RotationFollowsVelocity>,
     );
 
-    fn run(&mut self, (vel, mut rot, follow_rotation): Self::SystemData) {
-        for (vel, rot, _) in (&vel, &mut rot, &follow_rotation).join() {
-            // Point the rotation towards the velocity
-            rot.0 = f64::atan2(vel.y, vel.x);
+    fn run(&mut self, (dt, ang_vel, mut rot): Self::SystemData) {
+        let dt = dt.to_seconds();
+
+        for (ang_vel, rot) in (&ang_vel, &mut rot).join() {
+            rot.0 += ang_vel.0 * dt;
+        }
+    }
+}
+
+pub struct DragSystem;
+impl<'a> System<'a> for DragSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        ReadStorage<'a, Drag>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, AngularVelocity>,
+    );
+
+    fn run(&mut self, (dt, drag, mut vel, mut ang_vel): Self::SystemData) {
+        let dt = dt.to_seconds();
+
+        for (drag, vel) in (&drag, &mut vel).join() {
+            vel.0 *= (-drag.linear * dt).exp();
+        }
+        for (drag, ang_vel) in (&drag, &mut ang_vel).join() {
+            ang_vel.0 *= (-drag.angular * dt).exp();
         }
     }
 }
 
-pub struct CartesianRotationSystem;
-impl<'a> System<'a> for CartesianRotationSystem {
+pub struct RotationSystem;
+impl<'a> System<'a> for RotationSystem {
     type SystemData = (
-        ReadStorage<'a, CartesianVelocity>,
+        ReadStorage<'a, Velocity>,
         WriteStorage<'a, Rotation>,
         ReadStorage<'a, RotationFollowsVelocity>,
     );
@@ -111,7 +227,7 @@ impl<'a> System<'a> for CartesianRotationSystem {
     fn run(&mut self, (vel, mut rot, follow_rotation): Self::SystemData) {
         for (vel, rot, _) in (&vel, &mut rot, &follow_rotation).join() {
             // Point the rotation towards the velocity
-            rot.0 = -vel.rot.to_degrees();
+            rot.0 = f64::atan2(vel.y, vel.x);
         }
     }
 }
@@ -120,19 +236,52 @@ pub struct SpritePositionSystem;
 impl<'a> System<'a> for SpritePositionSystem {
     type SystemData = (
         Read<'a, Camera>,
+        Read<'a, Interpolation>,
+        Read<'a, WorldBounds>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, PreviousPosition>,
+        ReadStorage<'a, Collider>,
         WriteStorage<'a, Sprite>,
         ReadStorage<'a, MovesWithCamera>,
     );
 
-    fn run(&mut self, (camera, pos, mut sprite, moves_with_camera): Self::SystemData) {
-        // Map the camera position when the entity moves with it
-        for (pos, sprite, _) in (&pos, &mut sprite, &moves_with_camera).join() {
-            let offset = camera.map_pos(&pos);
-            sprite.set_pos(offset.x as i32, offset.y as i32);
-        }
-        // Just set the normal position when it's stationary
-        for (pos, sprite, _) in (&pos, &mut sprite, !&moves_with_camera).join() {
+    fn run(
+        &mut self,
+        (camera, alpha, bounds, pos, prev_pos, collider, mut sprite, moves_with_camera): Self::SystemData,
+    ) {
+        let camera = camera.interpolate(alpha.0);
+
+        for (pos, prev_pos, collider, sprite, moves_with_camera) in (
+            &pos,
+            prev_pos.maybe(),
+            collider.maybe(),
+            &mut sprite,
+            moves_with_camera.maybe(),
+        )
+            .join()
+        {
+            // Render the position between the previous and the current physics step
+            let pos = match prev_pos {
+                Some(prev_pos) => Position(Vec2::lerp(prev_pos.0, pos.0, alpha.0)),
+                None => Position(pos.0),
+            };
+
+            let pos = if moves_with_camera.is_some() {
+                // The sprite is drawn rotated around the center of its unrotated box, so the
+                // center has to be mapped instead of the top left corner
+                let offset = collider.map(|collider| collider.offset).unwrap_or_default();
+
+                // Draw the copy closest to the camera, so objects near the seam are visible on both
+                // sides
+                let center = Position(bounds.nearest(pos.0 + offset, camera.center()));
+
+                // Map the camera position when the entity moves with it
+                camera.map_pos(&center) - offset
+            } else {
+                // Just set the normal position when it's stationary
+                pos.0
+            };
+
             sprite.set_pos(pos.x as i32, pos.y as i32);
         }
     }
@@ -142,12 +291,15 @@ pub struct SpriteRotationSystem;
 impl<'a> System<'a> for SpriteRotationSystem {
     type SystemData = (
         Read<'a, Camera>,
+        Read<'a, Interpolation>,
         ReadStorage<'a, Rotation>,
         WriteStorage<'a, Sprite>,
         ReadStorage<'a, RotatesWithCamera>,
     );
 
-    fn run(&mut self, (camera, rot, mut sprite, rotates_with_camera): Self::SystemData) {
+    fn run(&mut self, (camera, alpha, rot, mut sprite, rotates_with_camera): Self::SystemData) {
+        let camera = camera.interpolate(alpha.0);
+
         // Map the camera rotation when the entity moves with it
         for (rot, sprite, _) in (&rot, &mut sprite, &rotates_with_camera).join() {
             sprite.set_rot((camera.map_rot(rot).to_degrees() + 90.0) as i16);
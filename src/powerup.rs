@@ -0,0 +1,312 @@
+use crate::{
+    collision::{Collider, CollisionEvent},
+    game::{Lives, Shield},
+    physics::{DeltaTime, Position, Velocity},
+    projectile::{LaserGun, Lifetime, WeaponModifier},
+    sprite::{self, generate},
+    user::{MovesWithCamera, Player},
+};
+use anyhow::Result;
+use rand::Rng;
+use specs::{prelude::*, shrev::EventChannel, Component, DenseVecStorage};
+use specs_blit::{Sprite, SpriteRef};
+use sprite_gen::{MaskValue, MaskValue::*, Options};
+
+type Vec2 = vek::Vec2<f64>;
+
+/// Seconds a power-up keeps working after it's picked up.
+#[const_tweaker::tweak(min = 0.0, max = 60.0, step = 0.5)]
+const POWERUP_TIME: f64 = 10.0;
+/// Seconds a dropped power-up floats around before it disappears.
+#[const_tweaker::tweak(min = 0.0, max = 60.0, step = 0.5)]
+const POWERUP_LIFETIME: f64 = 12.0;
+/// Part of the velocity of the destroyed object the power-up drifts away with.
+#[const_tweaker::tweak(min = 0.0, max = 1.0, step = 0.01)]
+const POWERUP_DRIFT: f64 = 0.5;
+/// Shield points regenerated every second while the shield boost is active.
+#[const_tweaker::tweak(min = 0.0, max = 20.0, step = 0.1)]
+const SHIELD_BOOST_REGEN: f64 = 5.0;
+
+const OPTIONS: Options = Options {
+    mirror_x: true,
+    mirror_y: false,
+    colored: true,
+    edge_brightness: 0.5,
+    color_variations: 0.1,
+    brightness_noise: 0.2,
+    saturation: 0.8,
+};
+
+/// The width of the left half of the masks, they are mirrored.
+const MASK_WIDTH: usize = 5;
+
+/// Three beams fanning out.
+const SPREAD_SHOT_MASK: [MaskValue; 45] = [
+    Body2, Empty, Empty, Empty, Body2, Body2, Body2, Empty, Empty, Body2, Empty, Body2, Empty,
+    Empty, Body2, Empty, Body2, Body2, Empty, Body2, Empty, Empty, Body2, Empty, Body2, Empty,
+    Empty, Body2, Body2, Body2, Empty, Empty, Empty, Body2, Body2, Empty, Empty, Empty, Solid,
+    Solid, Empty, Empty, Empty, Solid, Solid,
+];
+
+/// An arrow pointing up.
+const RAPID_FIRE_MASK: [MaskValue; 45] = [
+    Empty, Empty, Empty, Empty, Body2, Empty, Empty, Empty, Body2, Body2, Empty, Empty, Body2,
+    Body2, Body2, Empty, Body2, Body2, Body2, Body2, Body2, Body2, Empty, Body2, Body2, Empty,
+    Empty, Empty, Body2, Body2, Empty, Empty, Empty, Body2, Body2, Empty, Empty, Empty, Body2,
+    Body2, Empty, Empty, Empty, Solid, Solid,
+];
+
+/// A ring.
+const SHIELD_BOOST_MASK: [MaskValue; 45] = [
+    Empty, Empty, Body2, Body2, Body2, Empty, Body2, Body2, Empty, Empty, Body2, Body2, Empty,
+    Empty, Empty, Body2, Empty, Empty, Empty, Empty, Body2, Empty, Empty, Empty, Empty, Body2,
+    Body2, Empty, Empty, Empty, Empty, Body2, Body2, Empty, Empty, Empty, Empty, Body2, Body2,
+    Body2, Empty, Empty, Empty, Empty, Empty,
+];
+
+/// A plus sign.
+const EXTRA_LIFE_MASK: [MaskValue; 45] = [
+    Empty, Empty, Empty, Body2, Body2, Empty, Empty, Empty, Body2, Body2, Empty, Empty, Empty,
+    Body2, Body2, Body2, Body2, Body2, Body2, Body2, Body2, Body2, Body2, Body2, Body2, Empty,
+    Empty, Empty, Body2, Body2, Empty, Empty, Empty, Body2, Body2, Empty, Empty, Empty, Body2,
+    Body2, Empty, Empty, Empty, Empty, Empty,
+];
+
+/// What a power-up does when it's picked up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerUpKind {
+    /// Shoot three shorter beams at the same time.
+    SpreadShot,
+    /// Shoot a lot faster.
+    RapidFire,
+    /// Recharge the shield completely and keep recharging it fast.
+    ShieldBoost,
+    /// Get another life, this one isn't timed.
+    ExtraLife,
+}
+
+impl PowerUpKind {
+    /// All kinds of power-ups.
+    pub const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::SpreadShot,
+        PowerUpKind::RapidFire,
+        PowerUpKind::ShieldBoost,
+        PowerUpKind::ExtraLife,
+    ];
+
+    /// The name shown on the HUD.
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpKind::SpreadShot => "SPREAD SHOT",
+            PowerUpKind::RapidFire => "RAPID FIRE",
+            PowerUpKind::ShieldBoost => "SHIELD BOOST",
+            PowerUpKind::ExtraLife => "EXTRA LIFE",
+        }
+    }
+
+    /// How the gun of the player changes while the power-up is active.
+    pub fn modifier(self) -> WeaponModifier {
+        match self {
+            PowerUpKind::SpreadShot => WeaponModifier {
+                strength: 0.75,
+                count: 3,
+                spread: 0.15,
+                ..WeaponModifier::default()
+            },
+            PowerUpKind::RapidFire => WeaponModifier {
+                cooldown: 0.35,
+                ..WeaponModifier::default()
+            },
+            PowerUpKind::ShieldBoost | PowerUpKind::ExtraLife => WeaponModifier::default(),
+        }
+    }
+
+    /// The mask the sprite is generated from, every kind has its own shape.
+    fn mask(self) -> &'static [MaskValue] {
+        match self {
+            PowerUpKind::SpreadShot => &SPREAD_SHOT_MASK,
+            PowerUpKind::RapidFire => &RAPID_FIRE_MASK,
+            PowerUpKind::ShieldBoost => &SHIELD_BOOST_MASK,
+            PowerUpKind::ExtraLife => &EXTRA_LIFE_MASK,
+        }
+    }
+}
+
+/// A power-up floating around waiting to be picked up by the player.
+#[derive(Component, Debug)]
+pub struct PowerUp {
+    pub kind: PowerUpKind,
+}
+
+/// The power-ups the player has picked up with the seconds they have left.
+#[derive(Component, Debug, Default)]
+pub struct ActivePowerUps {
+    active: Vec<(PowerUpKind, f64)>,
+}
+
+impl ActivePowerUps {
+    /// Start a power-up, picking up one that's already active restarts its time.
+    pub fn activate(&mut self, kind: PowerUpKind, time: f64) {
+        match self.active.iter_mut().find(|(active, _)| *active == kind) {
+            Some((_, time_left)) => *time_left = time,
+            None => self.active.push((kind, time)),
+        }
+    }
+
+    /// Whether the power-up is active.
+    pub fn contains(&self, kind: PowerUpKind) -> bool {
+        self.active.iter().any(|(active, _)| *active == kind)
+    }
+
+    /// The active power-ups with the seconds they have left, in the order they were picked up.
+    pub fn iter(&self) -> impl Iterator<Item = &(PowerUpKind, f64)> {
+        self.active.iter()
+    }
+}
+
+/// Pregenerated sprites with their colliders for every kind of power-up, sprites are never freed so
+/// they are reused for every drop.
+pub struct PowerUpSprites(Vec<(PowerUpKind, SpriteRef, Collider)>);
+
+impl PowerUpSprites {
+    /// Generate a sprite for every kind.
+    pub fn new<R: Rng>(rng: &mut R) -> Result<Self> {
+        PowerUpKind::ALL
+            .iter()
+            .map(|&kind| {
+                let sprite = generate(MASK_WIDTH, OPTIONS, kind.mask(), 1, rng)?;
+                let collider = Collider::circle(sprite::size(MASK_WIDTH, OPTIONS, kind.mask()));
+
+                Ok((kind, sprite, collider))
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+/// The chance between 0 and 1 that a destroyed asteroid or enemy drops a power-up, it's set by
+/// the current wave.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DropChance(pub f64);
+
+/// Drop a random power-up at the center of a destroyed object depending on the chance.
+pub fn maybe_drop<R: Rng>(
+    entities: &Entities,
+    updater: &LazyUpdate,
+    rng: &mut R,
+    sprites: &PowerUpSprites,
+    chance: DropChance,
+    center: Vec2,
+    vel: Vec2,
+) {
+    if !rng.gen_bool(chance.0.clamp(0.0, 1.0)) {
+        return;
+    }
+
+    let (kind, sprite, collider) = sprites.0[rng.gen_range(0, sprites.0.len())].clone();
+
+    // It doesn't rotate so the shape is always upright on the screen
+    updater
+        .create_entity(entities)
+        .with(PowerUp { kind })
+        .with(Position(center - collider.offset))
+        .with(Velocity(vel * *POWERUP_DRIFT))
+        .with(Lifetime(*POWERUP_LIFETIME))
+        .with(MovesWithCamera)
+        .with(Sprite::new(sprite))
+        .with(collider)
+        .build();
+}
+
+/// Let the player pick up power-ups and apply the active ones to its gun and shield.
+#[derive(Default)]
+pub struct PowerUpSystem {
+    reader: Option<ReaderId<CollisionEvent>>,
+}
+impl<'a> System<'a> for PowerUpSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        Write<'a, Lives>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, PowerUp>,
+        WriteStorage<'a, ActivePowerUps>,
+        WriteStorage<'a, LaserGun>,
+        WriteStorage<'a, Shield>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (entities, dt, events, mut lives, player, power_up, mut active, mut gun, mut shield): Self::SystemData,
+    ) {
+        let dt = dt.to_seconds();
+
+        // The player can touch the same power-up with multiple events in a single step
+        let mut picked_up = vec![];
+
+        for event in events.read(self.reader.as_mut().unwrap()) {
+            // The player can be either side of the collision
+            let (player_entity, other) = if player.contains(event.entity) {
+                (event.entity, event.other)
+            } else if player.contains(event.other) {
+                (event.other, event.entity)
+            } else {
+                continue;
+            };
+
+            let kind = match power_up.get(other) {
+                Some(power_up) if !picked_up.contains(&other) => power_up.kind,
+                _ => continue,
+            };
+            picked_up.push(other);
+            let _ = entities.delete(other);
+
+            match kind {
+                PowerUpKind::ExtraLife => lives.0 += 1,
+                kind => {
+                    if let Some(active) = active.get_mut(player_entity) {
+                        active.activate(kind, *POWERUP_TIME);
+                    }
+                    if kind == PowerUpKind::ShieldBoost {
+                        if let Some(shield) = shield.get_mut(player_entity) {
+                            shield.current = shield.max;
+                        }
+                    }
+                }
+            }
+        }
+
+        for (entity, active) in (&entities, &mut active).join() {
+            // Count down and remove the power-ups that ran out
+            for (_, time_left) in active.active.iter_mut() {
+                *time_left -= dt;
+            }
+            active.active.retain(|(_, time_left)| *time_left > 0.0);
+
+            if let Some(gun) = gun.get_mut(entity) {
+                gun.modifier = active
+                    .iter()
+                    .fold(WeaponModifier::default(), |modifier, (kind, _)| {
+                        modifier.combine(kind.modifier())
+                    });
+            }
+
+            if active.contains(PowerUpKind::ShieldBoost) {
+                if let Some(shield) = shield.get_mut(entity) {
+                    shield.current = (shield.current + *SHIELD_BOOST_REGEN * dt).min(shield.max);
+                }
+            }
+        }
+    }
+}
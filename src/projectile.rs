@@ -1,5 +1,17 @@
-use crate::{audio::Audio, physics::DeltaTime, sprite::Line};
-use specs::{prelude::*, Component, DenseVecStorage};
+use crate::{
+    asteroid::Asteroid,
+    audio::Audio,
+    collision::{self, Collider, CollisionEvent, RaycastHit, Shape},
+    particle,
+    physics::{DeltaTime, Position, PreviousPosition, Rotation, Velocity, WorldBounds},
+    powerup::PowerUp,
+    random::WorldRng,
+    rocket::Rocket,
+    sprite::Line,
+    user::MovesWithCamera,
+    weapon::{Weapon, WeaponKind},
+};
+use specs::{prelude::*, shrev::EventChannel, Component, DenseVecStorage};
 
 type Vec2 = vek::Vec2<f64>;
 
@@ -7,10 +19,182 @@ type Vec2 = vek::Vec2<f64>;
 const LASER_LIFETIME: f64 = 0.1;
 #[const_tweaker::tweak(min = 0.0, max = 5000.0, step = 1.0)]
 const LASER_DISSIPATION_FACTOR: f64 = 3000.0;
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const LASER_DAMAGE: f64 = 1.0;
+#[const_tweaker::tweak(min = 0.0, max = 2000.0, step = 1.0)]
+const LASER_STRENGTH: f64 = 500.0;
+#[const_tweaker::tweak(min = 0.0, max = 2.0, step = 0.01)]
+const LASER_FIRE_DELAY: f64 = 0.15;
+/// How fast missiles can change their direction, higher is more agile.
+#[const_tweaker::tweak(min = 0.0, max = 20.0, step = 0.1)]
+const MISSILE_STEERING: f64 = 4.0;
+/// Missiles only home in on targets closer than this.
+#[const_tweaker::tweak(min = 0.0, max = 2000.0, step = 10.0)]
+const MISSILE_RANGE: f64 = 400.0;
+/// Mines explode when anything gets closer than this.
+#[const_tweaker::tweak(min = 0.0, max = 200.0, step = 1.0)]
+const MINE_TRIGGER_RADIUS: f64 = 20.0;
+/// Everything closer than this gets damaged when a mine explodes.
+#[const_tweaker::tweak(min = 0.0, max = 500.0, step = 1.0)]
+const MINE_BLAST_RADIUS: f64 = 60.0;
+
+// The bytes of the pixels are shown as red, green & blue, so the colors are written as 0xBBGGRR
+const MINE_EXPLOSION_COLOR: u32 = 0x00_88_FF;
 
 /// A laser beam.
 #[derive(Component, Debug)]
-pub struct Laser;
+pub struct Laser {
+    /// The entity that fired the laser, it can't be hit by it.
+    pub owner: Entity,
+    /// How much damage the laser does to the object it hits.
+    pub damage: f64,
+    /// The first object in the path of the beam, the beam ends there.
+    pub hit: Option<RaycastHit>,
+    /// Whether the hit has already been reported, it's instant so this only happens once.
+    pub resolved: bool,
+}
+
+impl Laser {
+    /// Instantiate a new laser that still has to report its hit.
+    pub fn new(owner: Entity, damage: f64, hit: Option<RaycastHit>) -> Self {
+        Self {
+            owner,
+            damage,
+            hit,
+            resolved: false,
+        }
+    }
+}
+
+/// Changes to how a gun shoots on top of its own settings, power-ups apply these for a while.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeaponModifier {
+    /// Multiplier for the length of the laser beams.
+    pub strength: f64,
+    /// Amount of beams shot at the same time.
+    pub count: usize,
+    /// The angle in radians between the beams when more than one is shot.
+    pub spread: f64,
+    /// Multiplier for the seconds between shots.
+    pub cooldown: f64,
+}
+
+impl WeaponModifier {
+    /// Apply another modifier on top of this one.
+    pub fn combine(self, other: Self) -> Self {
+        Self {
+            strength: self.strength * other.strength,
+            count: self.count.max(other.count),
+            spread: self.spread.max(other.spread),
+            cooldown: self.cooldown * other.cooldown,
+        }
+    }
+}
+
+impl Default for WeaponModifier {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            count: 1,
+            spread: 0.0,
+            cooldown: 1.0,
+        }
+    }
+}
+
+/// A gun shooting lasers from the nose of a rocket in the direction it's heading.
+#[derive(Component, Debug)]
+pub struct LaserGun {
+    /// The distance from the center of the rocket to the nose.
+    pub nose: f64,
+    /// The length of the laser beams.
+    pub strength: f64,
+    /// Seconds between shots.
+    pub fire_delay: f64,
+    /// Seconds left before the gun can shoot again.
+    pub cooldown: f64,
+    /// Whether the gun keeps shooting while the trigger is held.
+    pub autofire: bool,
+    /// The point in the world the gun aims at, when it's not set the gun shoots in the direction
+    /// the rocket is heading.
+    pub aim: Option<Vec2>,
+    /// Whether the trigger is pulled.
+    pub trigger: bool,
+    /// Whether the trigger was pulled the previous step, without autofire every pull shoots once.
+    pub was_triggered: bool,
+    /// Changes to the beams and the fire delay from power-ups.
+    pub modifier: WeaponModifier,
+}
+
+impl LaserGun {
+    /// Instantiate a new gun with a nose at the distance from the center.
+    pub fn new(nose: f64) -> Self {
+        Self {
+            nose,
+            strength: *LASER_STRENGTH,
+            fire_delay: *LASER_FIRE_DELAY,
+            cooldown: 0.0,
+            autofire: false,
+            aim: None,
+            trigger: false,
+            was_triggered: false,
+            modifier: WeaponModifier::default(),
+        }
+    }
+
+    /// Count down the cooldown and check whether the gun shoots this step, when it does the
+    /// cooldown starts again from the fire delay.
+    pub fn pull(&mut self, dt: f64, fire_delay: f64) -> bool {
+        self.cooldown = (self.cooldown - dt).max(0.0);
+
+        let pulled = self.trigger && (self.autofire || !self.was_triggered);
+        self.was_triggered = self.trigger;
+
+        if !pulled || self.cooldown > 0.0 {
+            return false;
+        }
+        self.cooldown = fire_delay * self.modifier.cooldown;
+
+        true
+    }
+}
+
+/// What a projectile does when it's flying.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectileKind {
+    /// Flies straight ahead.
+    Bullet,
+    /// Steers towards the nearest target in front of it.
+    Missile,
+    /// Stays where it's dropped and explodes when something comes close.
+    Mine,
+}
+
+/// A physical object shot by a weapon, it damages the first object it hits.
+///
+/// Unlike a laser it takes time to reach its target.
+#[derive(Component, Debug)]
+pub struct Projectile {
+    /// The entity that fired the projectile, it can't be hit by it.
+    pub owner: Entity,
+    /// How much damage the projectile does to the object it hits.
+    pub damage: f64,
+    pub kind: ProjectileKind,
+}
+
+/// The entity that fired whatever caused a collision together with the damage it does, when it's a
+/// laser or a projectile.
+pub fn damage_source(
+    entity: Entity,
+    laser: &ReadStorage<Laser>,
+    projectile: &ReadStorage<Projectile>,
+) -> Option<(Entity, f64)> {
+    match (laser.get(entity), projectile.get(entity)) {
+        (Some(laser), _) => Some((laser.owner, laser.damage)),
+        (None, Some(projectile)) => Some((projectile.owner, projectile.damage)),
+        (None, None) => None,
+    }
+}
 
 /// Object that will be destroyed when the time is up.
 #[derive(Component, Debug, Default)]
@@ -49,31 +233,308 @@ impl<'a> System<'a> for LaserLifetimeSystem {
     fn run(&mut self, (laser, lifetime, mut line): Self::SystemData) {
         for (_, lifetime, line) in (&laser, &lifetime, &mut line).join() {
             // Reduce the color depending on the lifetime left
-            let color_byte = (lifetime.0 * *LASER_DISSIPATION_FACTOR).min(255.0).max(0.0) as u32;
+            let color_byte = (lifetime.0 * *LASER_DISSIPATION_FACTOR).clamp(0.0, 255.0) as u32;
             line.color = (color_byte & 0xF) << 16 | (color_byte & 0xF) << 8 | color_byte;
         }
     }
 }
 
-/// Shoot a laser by spawning a new laser object.
-pub fn shoot_laser(world: &mut World, pos: &Vec2, dir: f64, strength: f64) {
+pub struct LaserGunSystem;
+impl<'a> System<'a> for LaserGunSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, WorldBounds>,
+        Read<'a, LazyUpdate>,
+        Write<'a, Audio>,
+        Write<'a, WorldRng>,
+        WriteStorage<'a, LaserGun>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, PowerUp>,
+        ReadStorage<'a, Weapon>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            dt,
+            bounds,
+            updater,
+            mut audio,
+            mut rng,
+            mut gun,
+            pos,
+            rot,
+            collider,
+            power_up,
+            weapon,
+        ): Self::SystemData,
+    ) {
+        let dt = dt.to_seconds();
+
+        for (entity, gun, gun_pos, rot, weapon) in
+            (&entities, &mut gun, &pos, &rot, weapon.maybe()).join()
+        {
+            // The other weapons are fired by the weapon system
+            if weapon.is_some_and(|weapon| weapon.kind != WeaponKind::Laser) {
+                continue;
+            }
+
+            let fire_delay = gun.fire_delay;
+            if !gun.pull(dt, fire_delay) {
+                continue;
+            }
+
+            // Shoot from the nose of the rocket
+            let center = match collider.get(entity) {
+                Some(collider) => gun_pos.0 + collider.offset,
+                None => gun_pos.0,
+            };
+            let heading = Vec2::new(rot.cos(), rot.sin());
+            let aim = match gun.aim {
+                Some(target) if target != center => (target - center).normalized(),
+                _ => heading,
+            };
+            let strength = gun.strength * gun.modifier.strength;
+
+            // Fan the beams out around the aim
+            let count = gun.modifier.count.max(1);
+            for index in 0..count {
+                let offset = (index as f64 - (count - 1) as f64 / 2.0) * gun.modifier.spread;
+                let (sin, cos) = offset.sin_cos();
+                let dir = Vec2::new(aim.x * cos - aim.y * sin, aim.x * sin + aim.y * cos);
+                let nose = center + dir * gun.nose;
+
+                // Find the first object in the path of the beam
+                let hit = collision::raycast(
+                    (&entities, &pos, &collider, !&power_up)
+                        .join()
+                        .map(|(entity, pos, collider, _)| (entity, pos, collider)),
+                    &bounds,
+                    nose,
+                    dir,
+                    strength,
+                    entity,
+                );
+
+                if let Some(hit) = hit {
+                    particle::sparks(&entities, &updater, &mut *rng, hit.point, dir);
+                }
+
+                shoot_laser(
+                    &entities,
+                    &updater,
+                    Laser::new(entity, *LASER_DAMAGE, hit),
+                    &nose,
+                    dir.y.atan2(dir.x),
+                    strength,
+                );
+            }
+
+            // Play a laser sound
+            audio.play_laser();
+        }
+    }
+}
+
+/// Shoot a laser by spawning a new laser object, the direction is in radians.
+///
+/// When the beam hits an object it's cut off at the hit point, and the object will take damage.
+pub fn shoot_laser(
+    entities: &Entities,
+    updater: &LazyUpdate,
+    laser: Laser,
+    pos: &Vec2,
+    dir: f64,
+    strength: f64,
+) -> Entity {
+    let line = match laser.hit {
+        Some(hit) => Line {
+            p1: *pos,
+            p2: hit.point,
+            color: 0xFF_FF_FF,
+        },
+        None => Line::from_direction(pos, dir, strength, 0xFF_FF_FF),
+    };
+
     // Create the laser entity
-    world
-        .create_entity()
-        .with(Laser)
+    updater
+        .create_entity(entities)
+        .with(laser)
         // The lifetime of the laser depends on the strength
         .with(Lifetime(*LASER_LIFETIME))
-        .with(Line::from_direction(
+        .with(line)
+        .with(MovesWithCamera)
+        .build()
+}
+
+/// Check what the projectiles hit while moving this step, steer the missiles and set off the mines.
+///
+/// Hits are reported as collision events with the projectile as the entity, like lasers.
+pub struct ProjectileSystem;
+impl<'a> System<'a> for ProjectileSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, WorldBounds>,
+        Read<'a, LazyUpdate>,
+        Write<'a, WorldRng>,
+        Write<'a, EventChannel<CollisionEvent>>,
+        ReadStorage<'a, Projectile>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, PreviousPosition>,
+        WriteStorage<'a, Velocity>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, Asteroid>,
+        ReadStorage<'a, Rocket>,
+        ReadStorage<'a, PowerUp>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            dt,
+            bounds,
+            updater,
+            mut rng,
+            mut events,
+            projectile,
             pos,
-            dir.to_radians(),
-            strength,
-            0xFF_FF_FF,
-        ))
-        .build();
-
-    // Play a laser sound
-    {
-        let mut audio = world.write_resource::<Audio>();
-        audio.play_laser();
+            prev_pos,
+            mut vel,
+            collider,
+            asteroid,
+            rocket,
+            power_up,
+        ): Self::SystemData,
+    ) {
+        let dt = dt.to_seconds();
+
+        // Everything that can be hit, power-ups are only picked up
+        let targets = (&entities, &pos, &collider, !&power_up)
+            .join()
+            .map(|(entity, pos, collider, _)| (entity, pos, collider))
+            .collect::<Vec<_>>();
+
+        for (entity, projectile, pos, prev_pos) in (&entities, &projectile, &pos, &prev_pos).join()
+        {
+            if projectile.kind == ProjectileKind::Mine {
+                // Everything but the owner overlapping a circle around the mine
+                let (targets, bounds) = (&targets, &*bounds);
+                let in_range = |radius: f64| {
+                    targets
+                        .iter()
+                        .filter(move |(target, _, _)| *target != projectile.owner)
+                        .filter_map(move |(target, target_pos, target_collider)| {
+                            let center =
+                                bounds.nearest(target_pos.0 + target_collider.offset, pos.0);
+
+                            Shape::Circle(radius)
+                                .overlaps(pos.0, &target_collider.shape, center)
+                                .then_some((*target, center))
+                        })
+                };
+
+                // Explode when anything comes close, the owner can fly over it safely
+                if in_range(*MINE_TRIGGER_RADIUS).next().is_none()
+                    || entities.delete(entity).is_err()
+                {
+                    continue;
+                }
+
+                // Damage everything in the blast
+                for (target, center) in in_range(*MINE_BLAST_RADIUS) {
+                    events.single_write(CollisionEvent {
+                        entity,
+                        other: target,
+                        point: center,
+                    });
+                }
+
+                particle::explosion(
+                    &entities,
+                    &updater,
+                    &mut *rng,
+                    pos.0,
+                    Vec2::zero(),
+                    32,
+                    MINE_EXPLOSION_COLOR,
+                );
+
+                continue;
+            }
+
+            // Cast a ray along the path the projectile moved so it can't pass through thin objects
+            let path = bounds.nearest(pos.0, prev_pos.0) - prev_pos.0;
+            let distance = path.magnitude();
+            if distance > 0.0 {
+                let hit = collision::raycast(
+                    targets.iter().copied(),
+                    &bounds,
+                    prev_pos.0,
+                    path / distance,
+                    distance,
+                    projectile.owner,
+                );
+
+                if let Some(hit) = hit {
+                    if entities.delete(entity).is_ok() {
+                        events.single_write(CollisionEvent {
+                            entity,
+                            other: hit.entity,
+                            point: hit.point,
+                        });
+
+                        particle::sparks(
+                            &entities,
+                            &updater,
+                            &mut *rng,
+                            hit.point,
+                            path / distance,
+                        );
+                    }
+
+                    continue;
+                }
+            }
+
+            if projectile.kind == ProjectileKind::Missile {
+                let vel = match vel.get_mut(entity) {
+                    Some(vel) => vel,
+                    None => continue,
+                };
+                let heading = vel.0.try_normalized().unwrap_or_else(Vec2::unit_x);
+
+                // Home in on the nearest asteroid or rocket in front of the missile
+                let speed = vel.0.magnitude();
+                let target = targets
+                    .iter()
+                    .filter(|(target, _, _)| {
+                        *target != projectile.owner
+                            && (asteroid.contains(*target) || rocket.contains(*target))
+                    })
+                    .map(|(_, target_pos, target_collider)| {
+                        bounds.nearest(target_pos.0 + target_collider.offset, pos.0)
+                    })
+                    .filter(|target| {
+                        let delta = *target - pos.0;
+
+                        delta.magnitude() < *MISSILE_RANGE && delta.dot(heading) > 0.0
+                    })
+                    .min_by(|a, b| a.distance(pos.0).partial_cmp(&b.distance(pos.0)).unwrap());
+
+                let desired = match target {
+                    Some(target) => (target - pos.0).try_normalized().unwrap_or(heading),
+                    None => heading,
+                } * speed;
+
+                // Turn towards the target instead of changing direction instantly
+                vel.0 += (desired - vel.0) * (*MISSILE_STEERING * dt).min(1.0);
+            }
+        }
     }
 }
@@ -0,0 +1,42 @@
+use rand::{rngs::StdRng, Error, RngCore, SeedableRng};
+
+/// The seed the world is generated with, the same seed always results in the same world.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Seed(pub u64);
+
+/// The random generator used by every spawn and generation path.
+///
+/// It's seeded from the world seed so a run can be reproduced, never use `rand::thread_rng()` for
+/// anything that affects the game.
+pub struct WorldRng(StdRng);
+
+impl WorldRng {
+    /// Instantiate a new generator from the world seed.
+    pub fn new(seed: Seed) -> Self {
+        Self(StdRng::seed_from_u64(seed.0))
+    }
+}
+
+impl Default for WorldRng {
+    fn default() -> Self {
+        Self::new(Seed::default())
+    }
+}
+
+impl RngCore for WorldRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
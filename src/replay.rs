@@ -0,0 +1,199 @@
+use crate::{
+    asteroid::Asteroid,
+    game::{GameState, Health, Lives, Score, Shield},
+    input::InputState,
+    physics::{Position, Rotation, Velocity},
+    Config,
+};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::Path,
+};
+
+/// The version of the recording files, it must be increased whenever the recorded input, the config
+/// or the checksum changes, like when an action is added.
+const FORMAT_VERSION: u32 = 2;
+
+/// A recorded session that can be played back exactly.
+///
+/// The world is generated from the seed in the config and the physics run at a fixed rate, so
+/// feeding the same input to every physics step results in the exact same game.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    /// The format the recording was saved with, it's always the first field so it can be checked
+    /// before reading the rest.
+    version: u32,
+    /// The settings the world was built with, including the seed.
+    pub config: Config,
+    /// The input of every physics step, input that's held for multiple steps is only stored once
+    /// together with the amount of steps.
+    steps: Vec<(u32, InputState)>,
+    /// Checksum of the world after the final step.
+    checksum: Option<u64>,
+}
+
+impl Recording {
+    /// Start a new empty recording for a world built with the config.
+    pub fn new(config: &Config) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            config: config.clone(),
+            steps: vec![],
+            checksum: None,
+        }
+    }
+
+    /// Add the input used for a single physics step.
+    pub fn record(&mut self, input: &InputState) {
+        match self.steps.last_mut() {
+            Some((frames, last)) if last == input => *frames += 1,
+            _ => self.steps.push((1, input.clone())),
+        }
+    }
+
+    /// The amount of recorded physics steps.
+    pub fn frames(&self) -> usize {
+        self.steps.iter().map(|(frames, _)| *frames as usize).sum()
+    }
+
+    /// The input for every recorded physics step.
+    pub fn inputs(&self) -> impl Iterator<Item = InputState> + '_ {
+        self.steps
+            .iter()
+            .flat_map(|(frames, input)| (0..*frames).map(move |_| input.clone()))
+    }
+
+    /// Remember the state of the world after the final step, so a replay can verify that it ended
+    /// up in exactly the same state.
+    pub fn finish(&mut self, world: &World) {
+        self.checksum = Some(checksum(world));
+    }
+
+    /// Check whether the world is in the same state as when the recording was finished.
+    ///
+    /// Returns `None` when the recording wasn't finished.
+    pub fn verify(&self, world: &World) -> Option<bool> {
+        self.checksum.map(|expected| expected == checksum(world))
+    }
+
+    /// Write the recording to a file.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let writer = BufWriter::new(File::create(path)?);
+        bincode::serialize_into(writer, self)?;
+
+        Ok(())
+    }
+
+    /// Read a recording from a file, recordings saved with another format version are rejected.
+    pub fn load(path: &Path) -> Result<Self> {
+        let bytes = fs::read(path)?;
+
+        // Check the version first, the rest can't be read when the format changed
+        let version: u32 = bincode::deserialize(&bytes)?;
+        if version != FORMAT_VERSION {
+            bail!(
+                "{}: the recording has format version {} but only version {} can be played back",
+                path.display(),
+                version,
+                FORMAT_VERSION
+            );
+        }
+
+        Ok(bincode::deserialize(&bytes)?)
+    }
+}
+
+/// Calculate a checksum of the state of all entities, when two worlds have the same checksum they
+/// are in the same state.
+///
+/// Entity ids aren't part of it, they depend on the order in which entities are created.
+pub fn checksum(world: &World) -> u64 {
+    let entities = world.entities();
+    let pos = world.read_storage::<Position>();
+    let vel = world.read_storage::<Velocity>();
+    let rot = world.read_storage::<Rotation>();
+    let asteroid = world.read_storage::<Asteroid>();
+    let health = world.read_storage::<Health>();
+    let shield = world.read_storage::<Shield>();
+
+    let mut states = (
+        &entities,
+        pos.maybe(),
+        vel.maybe(),
+        rot.maybe(),
+        asteroid.maybe(),
+        health.maybe(),
+        shield.maybe(),
+    )
+        .join()
+        .map(|(_, pos, vel, rot, asteroid, health, shield)| {
+            // Use the bits, the values must be exactly the same, every component starts with a
+            // different tag so a missing one can't be mistaken for another
+            let mut state = vec![];
+            if let Some(pos) = pos {
+                state.extend_from_slice(&[1, pos.x.to_bits(), pos.y.to_bits()]);
+            }
+            if let Some(vel) = vel {
+                state.extend_from_slice(&[2, vel.x.to_bits(), vel.y.to_bits()]);
+            }
+            if let Some(rot) = rot {
+                state.extend_from_slice(&[3, rot.0.to_bits()]);
+            }
+            if let Some(asteroid) = asteroid {
+                state.extend_from_slice(&[4, asteroid.health.to_bits()]);
+            }
+            if let Some(health) = health {
+                state.extend_from_slice(&[5, health.current.to_bits()]);
+            }
+            if let Some(shield) = shield {
+                state.extend_from_slice(&[6, shield.current.to_bits()]);
+            }
+
+            state
+        })
+        .filter(|state| !state.is_empty())
+        .collect::<Vec<_>>();
+    // Sort the entities by their state, so the order they are stored in doesn't matter
+    states.sort_unstable();
+
+    let mut hash = Fnv1a::new();
+    for state in &states {
+        hash.write(state.len() as u64);
+        for value in state {
+            hash.write(*value);
+        }
+    }
+
+    // The game can end up in a different state without any entity being different
+    hash.write(world.read_resource::<Score>().0);
+    hash.write(world.read_resource::<Lives>().0 as u64);
+    hash.write(*world.read_resource::<GameState>() as u64);
+
+    hash.finish()
+}
+
+/// The 64 bit FNV-1a hash, unlike the hasher of the standard library its output never changes
+/// between Rust releases or platforms, so stored checksums stay valid.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xCBF2_9CE4_8422_2325)
+    }
+
+    /// Hash the little endian bytes of the value.
+    fn write(&mut self, value: u64) {
+        for byte in value.to_le_bytes().iter() {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
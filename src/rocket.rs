@@ -1,22 +1,48 @@
 use crate::{
+    ai::{self, AiController},
+    collision::{Collider, CollisionEvent},
+    game::{self, GameState, Health, Invulnerable, Score, Shield},
+    particle::{self, Exhaust, ROCKET_EXPLOSION_COLOR},
     physics::*,
-    sprite::generate,
-    user::{MovesWithCamera, RotatesWithCamera},
+    powerup::{self, ActivePowerUps, DropChance, PowerUpSprites},
+    projectile::{damage_source, Laser, LaserGun, Projectile},
+    random::WorldRng,
+    sprite::{self, generate},
+    user::{MovesWithCamera, Player, RotatesWithCamera},
+    weapon::{Weapon, WeaponKind},
 };
 use anyhow::Result;
 use rand::prelude::*;
-use specs::{prelude::*, Component, DenseVecStorage};
+use specs::{prelude::*, shrev::EventChannel, Component, DenseVecStorage};
 use specs_blit::Sprite;
 use sprite_gen::{MaskValue::*, Options};
+use std::f64::consts::FRAC_PI_2;
+
+type Vec2 = vek::Vec2<f64>;
+
+#[const_tweaker::tweak(min = 0.0, max = 5.0, step = 0.01)]
+const DRAG: f64 = 0.1;
+#[const_tweaker::tweak(min = 0.0, max = 20.0, step = 0.1)]
+const ANGULAR_DRAG: f64 = 4.0;
+
+#[const_tweaker::tweak(min = 0.0, max = 100.0, step = 1.0)]
+const PLAYER_HEALTH: f64 = 10.0;
+#[const_tweaker::tweak(min = 0.0, max = 100.0, step = 1.0)]
+const PLAYER_SHIELD: f64 = 5.0;
+
+/// The score for destroying an enemy rocket.
+const ROCKET_POINTS: u64 = 200;
 
 #[derive(Component, Debug, Default)]
 pub struct Rocket {}
 
+/// Spawn small enemy rockets in an area with the size starting at the origin.
 pub fn spawn_small_rockets(
     world: &mut World,
     amount: usize,
-    screen_width: usize,
-    screen_height: usize,
+    origin: Vec2,
+    area_width: usize,
+    area_height: usize,
 ) -> Result<()> {
     let (width, _height, options) = (
         6,
@@ -40,37 +66,48 @@ pub fn spawn_small_rockets(
         Body1, Empty, Body1, Body1, Empty, Body1, Body1,
     ];
 
-    let mut rng = rand::thread_rng();
+    let collider = Collider::aabb(sprite::size(width, options, &rocket_mask));
 
     for _ in 0..amount {
-        // Generate the sprite
-        let sprite = generate(width, options, &rocket_mask, 16)?;
+        // Generate the sprite and the placement
+        let (sprite, pos, vel, ai, gun) = {
+            let mut rng = world.write_resource::<WorldRng>();
+
+            let sprite = generate(width, options, &rocket_mask, 16, &mut *rng)?;
+            let pos = Position::new(
+                origin.x + rng.gen_range(0, area_width) as f64,
+                origin.y + rng.gen_range(0, area_height) as f64,
+            );
+            let vel = Velocity::new(rng.gen_range(-10.0, 10.0), rng.gen_range(-10.0, 10.0));
+            let ai = AiController::random(&mut *rng);
+            let gun = ai::enemy_laser_gun(collider.offset.y, &mut *rng);
+
+            (sprite, pos, vel, ai, gun)
+        };
 
         // Add the entity to the ECS system
         world
             .create_entity()
             .with(Rocket::default())
-            .with(Position::new(
-                rng.gen_range(0, screen_width) as f64,
-                rng.gen_range(0, screen_height) as f64,
-            ))
-            .with(Velocity::new(
-                rng.gen_range(-10.0, 10.0),
-                rng.gen_range(-10.0, 10.0),
-            ))
+            .with(pos)
+            .with(vel)
             .with(Rotation(0.0))
             .with(RotationFollowsVelocity)
+            .with(ai)
+            .with(gun)
             .with(MovesWithCamera)
             .with(RotatesWithCamera)
             .with(Sprite::new(sprite))
+            .with(collider)
             .build();
     }
 
     Ok(())
 }
 
-pub fn spawn_rocket(world: &mut World, x: usize, y: usize) -> Result<()> {
-    let (width, height, options) = (
+/// Spawn the player rocket with its center at the position.
+pub fn spawn_rocket(world: &mut World, x: usize, y: usize) -> Result<Entity> {
+    let (width, _height, options) = (
         11,
         24,
         Options {
@@ -108,23 +145,157 @@ pub fn spawn_rocket(world: &mut World, x: usize, y: usize) -> Result<()> {
     ];
 
     // Generate the sprite
-    let sprite = generate(width, options, &rocket_mask, 45)?;
+    let sprite = generate(
+        width,
+        options,
+        &rocket_mask,
+        45,
+        &mut *world.write_resource::<WorldRng>(),
+    )?;
+    let collider = Collider::aabb(sprite::size(width, options, &rocket_mask));
 
     // Add the entity to the ECS system
-    world
+    let entity = world
         .create_entity()
         .with(Rocket::default())
+        .with(Player)
         .with(Position::new(
-            (x - width / 2) as f64,
-            (y - height / 2) as f64,
+            x as f64 - collider.offset.x,
+            y as f64 - collider.offset.y,
         ))
-        .with(Rotation(0.0))
-        .with(CartesianVelocity {
-            speed: 0.0,
-            rot: 0.0,
+        .with(Velocity::new(0.0, 0.0))
+        // Point upwards
+        .with(Rotation(-FRAC_PI_2))
+        .with(AngularVelocity(0.0))
+        .with(Drag {
+            linear: *DRAG,
+            angular: *ANGULAR_DRAG,
         })
+        .with(LaserGun::new(collider.offset.y))
+        .with(Weapon::new(WeaponKind::Laser))
+        .with(Exhaust::default())
+        .with(Health::new(*PLAYER_HEALTH))
+        .with(Shield::new(*PLAYER_SHIELD))
+        .with(ActivePowerUps::default())
+        // Don't get hit by whatever is spawned on top of the player
+        .with(Invulnerable::new())
+        .with(MovesWithCamera)
+        .with(RotatesWithCamera)
         .with(Sprite::new(sprite))
+        .with(collider)
         .build();
 
-    Ok(())
+    Ok(entity)
+}
+
+/// Destroy the enemy rockets hit by lasers and projectiles of the player or crashed into by the
+/// player.
+#[derive(Default)]
+pub struct RocketHitSystem {
+    reader: Option<ReaderId<CollisionEvent>>,
+}
+impl<'a> System<'a> for RocketHitSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, EventChannel<CollisionEvent>>,
+        Read<'a, LazyUpdate>,
+        Read<'a, DropChance>,
+        ReadExpect<'a, PowerUpSprites>,
+        Write<'a, Score>,
+        Write<'a, WorldRng>,
+        Read<'a, GameState>,
+        ReadStorage<'a, Laser>,
+        ReadStorage<'a, Projectile>,
+        ReadStorage<'a, Rocket>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Invulnerable>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Collider>,
+    );
+
+    fn setup(&mut self, world: &mut World) {
+        Self::SystemData::setup(world);
+
+        self.reader = Some(
+            world
+                .fetch_mut::<EventChannel<CollisionEvent>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            events,
+            updater,
+            chance,
+            powerup_sprites,
+            mut score,
+            mut rng,
+            state,
+            laser,
+            projectile,
+            rocket,
+            player,
+            invulnerable,
+            pos,
+            vel,
+            collider,
+        ): Self::SystemData,
+    ) {
+        // Multiple lasers can hit the same rocket in a single step
+        let mut destroyed = vec![];
+
+        for event in events.read(self.reader.as_mut().unwrap()) {
+            // Only handle lasers and projectiles of the player hitting enemy rockets, crashing into
+            // one counts as the player shooting it
+            let (owner, target) = match damage_source(event.entity, &laser, &projectile) {
+                Some((owner, _)) => (owner, event.other),
+                None => match game::crash(event, *state, &player, &invulnerable) {
+                    Some(crash) => crash,
+                    None => continue,
+                },
+            };
+            if !player.contains(owner)
+                || !rocket.contains(target)
+                || player.contains(target)
+                || destroyed.contains(&target)
+            {
+                continue;
+            }
+
+            if entities.delete(target).is_err() {
+                continue;
+            }
+            destroyed.push(target);
+            score.0 += ROCKET_POINTS;
+
+            if let (Some(pos), Some(vel), Some(collider)) =
+                (pos.get(target), vel.get(target), collider.get(target))
+            {
+                let center = pos.0 + collider.offset;
+
+                particle::explosion(
+                    &entities,
+                    &updater,
+                    &mut *rng,
+                    center,
+                    vel.0,
+                    16,
+                    ROCKET_EXPLOSION_COLOR,
+                );
+                powerup::maybe_drop(
+                    &entities,
+                    &updater,
+                    &mut *rng,
+                    &powerup_sprites,
+                    *chance,
+                    center,
+                    vel.0,
+                );
+            }
+        }
+    }
 }
@@ -1,11 +1,17 @@
+use crate::{
+    physics::{Interpolation, Position, WorldBounds},
+    user::{Camera, MovesWithCamera},
+};
 use anyhow::Result;
+use hsl::HSL;
 use line_drawing::Bresenham;
+use rand::Rng;
 use specs::{prelude::*, Component, DenseVecStorage};
 use specs_blit::{
     blit::{BlitBuffer, Color},
     PixelBuffer, SpriteRef,
 };
-use sprite_gen::{gen_sprite, MaskValue, Options};
+use sprite_gen::{MaskValue, Options};
 
 type Vec2 = vek::Vec2<f64>;
 
@@ -18,10 +24,10 @@ pub struct Line {
 }
 
 impl Line {
-    /// Create a new line which casts from a starting point to a direction.
+    /// Create a new line which casts from a starting point to a direction in radians.
     pub fn from_direction(pos: &Vec2, dir: f64, length: f64, color: u32) -> Self {
-        let x = pos.x + dir.sin() * length;
-        let y = pos.y + dir.cos() * length;
+        let x = pos.x + dir.cos() * length;
+        let y = pos.y + dir.sin() * length;
 
         Self {
             p1: *pos,
@@ -33,20 +39,40 @@ impl Line {
 
 pub struct LineSystem;
 impl<'a> System<'a> for LineSystem {
-    type SystemData = (Write<'a, PixelBuffer>, ReadStorage<'a, Line>);
+    type SystemData = (
+        Write<'a, PixelBuffer>,
+        Read<'a, Camera>,
+        Read<'a, Interpolation>,
+        Read<'a, WorldBounds>,
+        ReadStorage<'a, Line>,
+        ReadStorage<'a, MovesWithCamera>,
+    );
 
-    fn run(&mut self, (mut pixels, line): Self::SystemData) {
+    fn run(
+        &mut self,
+        (mut pixels, camera, alpha, bounds, line, moves_with_camera): Self::SystemData,
+    ) {
         let width = pixels.width() as i32;
         let height = pixels.height() as i32;
         let pixels = pixels.pixels_mut();
 
-        for line in line.join() {
-            for (x, y) in Bresenham::new(
-                (line.p1.x as i32, line.p1.y as i32),
-                (line.p2.x as i32, line.p2.y as i32),
-            ) {
-                if x >= width || y >= height {
-                    break;
+        let camera = camera.interpolate(alpha.0);
+
+        for (line, moves_with_camera) in (&line, moves_with_camera.maybe()).join() {
+            // Map the points to the screen when the line is in the world
+            let (p1, p2) = if moves_with_camera.is_some() {
+                // Draw the copy closest to the camera, keeping both points on the same side
+                let p1 = bounds.nearest(line.p1, camera.center());
+                let p2 = line.p2 + (p1 - line.p1);
+
+                (camera.map_pos(&Position(p1)), camera.map_pos(&Position(p2)))
+            } else {
+                (line.p1, line.p2)
+            };
+
+            for (x, y) in Bresenham::new((p1.x as i32, p1.y as i32), (p2.x as i32, p2.y as i32)) {
+                if x < 0 || y < 0 || x >= width || y >= height {
+                    continue;
                 }
 
                 pixels[x as usize + y as usize * width as usize] = line.color;
@@ -55,17 +81,28 @@ impl<'a> System<'a> for LineSystem {
     }
 }
 
+/// Calculate the size in pixels of a sprite that will be generated from a mask.
+pub fn size(width: usize, options: Options, mask: &[MaskValue]) -> Vec2 {
+    let height = mask.len() / width;
+
+    Vec2::new(
+        if options.mirror_x { width * 2 } else { width } as f64,
+        if options.mirror_y { height * 2 } else { height } as f64,
+    )
+}
+
 /// Generate a random sprite from a mask and return it as a blit buffer.
-pub fn generate(
+pub fn generate<R: Rng>(
     width: usize,
     options: Options,
     mask: &[MaskValue],
     rotations: u16,
+    rng: &mut R,
 ) -> Result<SpriteRef> {
     let buffer_width = if options.mirror_x { width * 2 } else { width };
 
     let buf = BlitBuffer::from_buffer(
-        &gen_sprite(&mask, width, options)
+        &gen_sprite(mask, width, options, rng)
             .into_iter()
             // Invert the colors
             .map(|p| p ^ 0xFF_FF_FF_FF)
@@ -76,3 +113,162 @@ pub fn generate(
 
     specs_blit::load(buf, rotations)
 }
+
+/// Generate the pixels of a sprite from a mask.
+///
+/// This is the algorithm of `sprite_gen::gen_sprite`, which always uses the thread random
+/// generator, with the random generator passed so the result can be reproduced from a seed.
+fn gen_sprite<R: Rng>(
+    mask: &[MaskValue],
+    mask_width: usize,
+    options: Options,
+    rng: &mut R,
+) -> Vec<u32> {
+    let mask_height = mask.len() / mask_width;
+
+    // Randomly fill the body, -1 is a border, 0 is empty and 1 is filled
+    let mut mask = mask
+        .iter()
+        .map(|value| match value {
+            MaskValue::Solid => -1,
+            MaskValue::Empty => 0,
+            // Either empty or filled
+            MaskValue::Body1 => rng.gen::<f32>().round() as i8,
+            // Either a border or filled
+            MaskValue::Body2 => (rng.gen::<f32>().round() as i8) * 2 - 1,
+        })
+        .collect::<Vec<_>>();
+
+    // Surround the filled pixels with borders
+    for y in 0..mask_height {
+        for x in 0..mask_width {
+            let index = x + y * mask_width;
+            if mask[index] <= 0 {
+                continue;
+            }
+
+            if y > 0 && mask[index - mask_width] == 0 {
+                mask[index - mask_width] = -1;
+            }
+            if y < mask_height - 1 && mask[index + mask_width] == 0 {
+                mask[index + mask_width] = -1;
+            }
+            if x > 0 && mask[index - 1] == 0 {
+                mask[index - 1] = -1;
+            }
+            if x < mask_width - 1 && mask[index + 1] == 0 {
+                mask[index + 1] = -1;
+            }
+        }
+    }
+
+    let colored = if options.colored {
+        color_mask(&mask, mask_width, mask_height, &options, rng)
+    } else {
+        mask.iter()
+            .map(|&value| if value == -1 { 0 } else { 0xFF_FF_FF_FF })
+            .collect()
+    };
+
+    // Mirror the result
+    let width = if options.mirror_x {
+        mask_width * 2
+    } else {
+        mask_width
+    };
+    let height = if options.mirror_y {
+        mask_height * 2
+    } else {
+        mask_height
+    };
+    let mut result = vec![0; width * height];
+    for y in 0..mask_height {
+        for x in 0..mask_width {
+            let value = colored[x + y * mask_width];
+            let mirrored_x = width - x - 1;
+            let mirrored_y = height - y - 1;
+
+            result[x + y * width] = value;
+            if options.mirror_x {
+                result[mirrored_x + y * width] = value;
+            }
+            if options.mirror_y {
+                result[x + mirrored_y * width] = value;
+            }
+            if options.mirror_x && options.mirror_y {
+                result[mirrored_x + mirrored_y * width] = value;
+            }
+        }
+    }
+
+    result
+}
+
+/// Give the filled pixels of the mask random colors with a gradient.
+fn color_mask<R: Rng>(
+    mask: &[i8],
+    mask_width: usize,
+    mask_height: usize,
+    options: &Options,
+    rng: &mut R,
+) -> Vec<u32> {
+    let mut result = vec![0xFF_FF_FF_FF; mask.len()];
+
+    let is_vertical_gradient = rng.gen::<f32>() > 0.5;
+    let saturation = (rng.gen::<f64>() * options.saturation).clamp(0.0, 1.0);
+    let mut hue = rng.gen::<f64>();
+
+    let variation_check = 1.0 - options.color_variations;
+    let brightness_inv = 1.0 - options.brightness_noise;
+
+    let (u_size, v_size) = if is_vertical_gradient {
+        (mask_height, mask_width)
+    } else {
+        (mask_width, mask_height)
+    };
+
+    for u in 0..u_size {
+        // Sometimes switch to a new color
+        let is_new_color =
+            ((rng.gen_range(-1.0, 1.0) + rng.gen_range(-1.0, 1.0) + rng.gen_range(-1.0, 1.0))
+                / 3.0f64)
+                .abs();
+        if is_new_color > variation_check {
+            hue = rng.gen::<f64>();
+        }
+
+        for v in 0..v_size {
+            let index = if is_vertical_gradient {
+                v + u * mask_width
+            } else {
+                u + v * mask_width
+            };
+
+            let value = mask[index];
+            if value == 0 {
+                continue;
+            }
+
+            let u_sin = ((u as f64 / u_size as f64) * std::f64::consts::PI).sin();
+            let brightness = u_sin * brightness_inv + rng.gen_range(0.0, options.brightness_noise);
+
+            let (mut r, mut g, mut b) = HSL {
+                h: hue,
+                s: saturation,
+                l: brightness,
+            }
+            .to_rgb();
+
+            // Make the edges darker
+            if value == -1 {
+                r = (r as f64 * options.edge_brightness) as u8;
+                g = (g as f64 * options.edge_brightness) as u8;
+                b = (b as f64 * options.edge_brightness) as u8;
+            }
+
+            result[index] = ((r as u32) << 16) | ((g as u32) << 8) | (b as u32);
+        }
+    }
+
+    result
+}
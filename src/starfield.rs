@@ -0,0 +1,153 @@
+use crate::{
+    physics::{Interpolation, Position, WorldBounds},
+    random::Seed,
+    user::Camera,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use specs::prelude::*;
+use specs_blit::PixelBuffer;
+
+type Vec2 = vek::Vec2<f64>;
+
+/// The size of the square tile of stars that's repeated to fill the screen.
+const TILE_SIZE: f64 = 512.0;
+/// Mixed into the seed so the stars don't line up with anything else generated from it.
+const SEED_SALT: u64 = 0x5EED_57A2;
+
+/// How far the layers move compared to the camera, the amount of stars in a tile, the size of the
+/// stars and their color, from the furthest layer to the nearest.
+///
+/// The bytes of the pixels are shown as red, green & blue, so the colors are written as 0xBBGGRR.
+const LAYERS: [(f64, usize, usize, u32); 3] = [
+    (0.05, 60, 1, 0x55_44_44),
+    (0.15, 30, 1, 0x99_88_88),
+    (0.35, 12, 2, 0xFF_EE_EE),
+];
+
+/// A single layer of stars that moves slower than the world, so it looks further away.
+#[derive(Debug)]
+struct Layer {
+    /// How much the layer moves with the camera, 0 doesn't move and 1 moves like the world.
+    parallax: f64,
+    /// Positions of the stars in a single tile.
+    stars: Vec<Vec2>,
+    /// Width and height of a star in pixels.
+    size: usize,
+    color: u32,
+    /// How far the layer has scrolled, within a single tile.
+    offset: Vec2,
+}
+
+/// Procedurally generated layers of stars drawn as the background.
+///
+/// It's generated from the seed so the sky always looks the same for the same world, but it uses
+/// its own random generator so it doesn't change anything that happens in the game.
+#[derive(Debug, Default)]
+pub struct Starfield {
+    layers: Vec<Layer>,
+    /// The center of the camera the previous time the stars were drawn.
+    last_center: Option<Vec2>,
+}
+
+impl Starfield {
+    /// Generate the layers of stars.
+    pub fn new(seed: Seed) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed.0 ^ SEED_SALT);
+
+        let layers = LAYERS
+            .iter()
+            .map(|&(parallax, amount, size, color)| Layer {
+                parallax,
+                stars: (0..amount)
+                    .map(|_| {
+                        Vec2::new(rng.gen_range(0.0, TILE_SIZE), rng.gen_range(0.0, TILE_SIZE))
+                    })
+                    .collect(),
+                size,
+                color,
+                offset: Vec2::zero(),
+            })
+            .collect();
+
+        Self {
+            layers,
+            last_center: None,
+        }
+    }
+
+    /// Scroll the layers with the movement of the camera since the previous time.
+    ///
+    /// The movement is taken across the world seam, so wrapping around doesn't make the stars jump.
+    fn scroll(&mut self, center: Vec2, bounds: &WorldBounds) {
+        let delta = match self.last_center {
+            Some(last_center) => bounds.nearest(center, last_center) - last_center,
+            None => center,
+        };
+        self.last_center = Some(center);
+
+        for layer in self.layers.iter_mut() {
+            let offset = layer.offset + delta * layer.parallax;
+            layer.offset = Vec2::new(
+                offset.x.rem_euclid(TILE_SIZE),
+                offset.y.rem_euclid(TILE_SIZE),
+            );
+        }
+    }
+}
+
+/// Draw the starfield into the pixel buffer, it must run before everything else is drawn.
+pub struct StarfieldSystem;
+impl<'a> System<'a> for StarfieldSystem {
+    type SystemData = (
+        Write<'a, PixelBuffer>,
+        Read<'a, Camera>,
+        Read<'a, Interpolation>,
+        Read<'a, WorldBounds>,
+        Write<'a, Starfield>,
+    );
+
+    fn run(&mut self, (mut buffer, camera, alpha, bounds, mut starfield): Self::SystemData) {
+        let width = buffer.width() as i32;
+        let height = buffer.height() as i32;
+        let pixels = buffer.pixels_mut();
+
+        let camera = camera.interpolate(alpha.0);
+        let center = camera.center();
+        starfield.scroll(center, &bounds);
+
+        // The screen is rotated with the camera, so the tiles must cover a circle around the
+        // center of the screen reaching the corners
+        let radius = ((width * width + height * height) as f64).sqrt() / 2.0;
+        let tiles = (radius / TILE_SIZE).ceil() as i32;
+
+        for layer in starfield.layers.iter() {
+            for star in layer.stars.iter() {
+                // Position of the star relative to the center of the screen in the first tile
+                let relative = Vec2::new(
+                    (star.x - layer.offset.x).rem_euclid(TILE_SIZE),
+                    (star.y - layer.offset.y).rem_euclid(TILE_SIZE),
+                );
+
+                for tile_y in -tiles..tiles {
+                    for tile_x in -tiles..tiles {
+                        let relative =
+                            relative + Vec2::new(tile_x as f64, tile_y as f64) * TILE_SIZE;
+                        if relative.magnitude_squared() > radius * radius {
+                            continue;
+                        }
+
+                        let pos = camera.map_pos(&Position(center + relative));
+
+                        let size = layer.size as i32;
+                        let (x, y) = (pos.x as i32, pos.y as i32);
+                        for py in y.max(0)..(y + size).min(height) {
+                            for px in x.max(0)..(x + size).min(width) {
+                                pixels[px as usize + py as usize * width as usize] = layer.color;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
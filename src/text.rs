@@ -0,0 +1,279 @@
+use specs::{prelude::*, Component, DenseVecStorage};
+use specs_blit::PixelBuffer;
+
+type Vec2 = vek::Vec2<f64>;
+
+/// Width of a single character in pixels, without the spacing.
+pub const CHAR_WIDTH: usize = 5;
+/// Height of a single character in pixels, without the spacing.
+pub const CHAR_HEIGHT: usize = 7;
+
+/// The first character in the font, everything before it is a control character.
+const FIRST_CHAR: u8 = b' ';
+
+/// A 5x7 font for the printable ASCII characters.
+///
+/// Every character is stored as 5 columns from left to right, the lowest bit of a column is the top
+/// pixel.
+const FONT: [[u8; CHAR_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00], // '"'
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // '#'
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62], // '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50], // '&'
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '''
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // '('
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // ')'
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // '*'
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08], // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00], // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02], // '/'
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // '0'
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46], // '2'
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // '3'
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39], // '5'
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03], // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36], // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00], // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00], // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14], // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08], // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06], // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // '@'
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // 'A'
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // 'B'
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // 'C'
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // 'D'
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // 'E'
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // 'F'
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // 'G'
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // 'H'
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // 'I'
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // 'J'
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // 'K'
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // 'L'
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // 'M'
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // 'N'
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // 'O'
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // 'P'
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // 'Q'
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31], // 'S'
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // 'T'
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // 'U'
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // 'V'
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63], // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07], // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43], // 'Z'
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20], // '\'
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04], // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40], // '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00], // '`'
+    [0x20, 0x54, 0x54, 0x54, 0x78], // 'a'
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x20], // 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18], // 'e'
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // 'f'
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // 'g'
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // 'h'
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // 'i'
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // 'j'
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // 'k'
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // 'l'
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // 'm'
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38], // 'o'
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // 'p'
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // 'q'
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x20], // 's'
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // 't'
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // 'u'
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // 'v'
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44], // 'x'
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // 'y'
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00], // '{'
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00], // '}'
+    [0x08, 0x04, 0x08, 0x10, 0x08], // '~'
+];
+
+/// How the text is placed relative to its position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    /// The position is the top left of the text.
+    Left,
+    /// The position is the top center of the text.
+    Center,
+    /// The position is the top right of the text.
+    Right,
+}
+
+/// Draw a string on the screen, the position is in screen pixels.
+///
+/// Every line is aligned separately, characters that are not in the font are drawn as `?`.
+#[derive(Component, Debug, Clone)]
+pub struct Text {
+    pub text: String,
+    pub pos: Vec2,
+    pub color: u32,
+    /// Every pixel of the font is drawn as a square of this size.
+    pub scale: usize,
+    pub alignment: Alignment,
+}
+
+impl Text {
+    /// Instantiate new white left aligned text at the position.
+    pub fn new<S: Into<String>>(text: S, x: f64, y: f64) -> Self {
+        Self {
+            text: text.into(),
+            pos: Vec2::new(x, y),
+            color: 0xFF_FF_FF,
+            scale: 1,
+            alignment: Alignment::Left,
+        }
+    }
+
+    /// Set the color.
+    pub fn with_color(mut self, color: u32) -> Self {
+        self.color = color;
+
+        self
+    }
+
+    /// Set the size of a single pixel of the font.
+    pub fn with_scale(mut self, scale: usize) -> Self {
+        self.scale = scale.max(1);
+
+        self
+    }
+
+    /// Set how the text is placed relative to its position.
+    pub fn with_alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+
+        self
+    }
+
+    /// The size of the text in pixels.
+    pub fn size(&self) -> (usize, usize) {
+        text_size(&self.text, self.scale)
+    }
+}
+
+/// Draw all text on top of everything else, it must run after the sprites are drawn.
+pub struct TextSystem;
+impl<'a> System<'a> for TextSystem {
+    type SystemData = (Write<'a, PixelBuffer>, ReadStorage<'a, Text>);
+
+    fn run(&mut self, (mut buffer, text): Self::SystemData) {
+        for text in text.join() {
+            draw_text(
+                &mut buffer,
+                &text.text,
+                text.pos,
+                text.color,
+                text.scale,
+                text.alignment,
+            );
+        }
+    }
+}
+
+/// The size in pixels a string takes up when drawn.
+pub fn text_size(text: &str, scale: usize) -> (usize, usize) {
+    let lines = text.lines().count();
+    let columns = text
+        .lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    (
+        line_width(columns, scale),
+        lines * (CHAR_HEIGHT + 1) * scale,
+    )
+}
+
+/// Draw a string into the pixel buffer, everything outside of the buffer is clipped.
+///
+/// This is what the `Text` component is drawn with, the HUD uses it directly for values that
+/// change every frame.
+pub fn draw_text(
+    buffer: &mut PixelBuffer,
+    text: &str,
+    pos: Vec2,
+    color: u32,
+    scale: usize,
+    alignment: Alignment,
+) {
+    let width = buffer.width() as i32;
+    let height = buffer.height() as i32;
+    let pixels = buffer.pixels_mut();
+
+    let scale = scale.max(1) as i32;
+
+    for (line_index, line) in text.lines().enumerate() {
+        // Move the line so it's aligned around the position
+        let line_width = line_width(line.chars().count(), scale as usize) as f64;
+        let x = match alignment {
+            Alignment::Left => pos.x,
+            Alignment::Center => pos.x - line_width / 2.0,
+            Alignment::Right => pos.x - line_width,
+        } as i32;
+        let y = pos.y as i32 + line_index as i32 * (CHAR_HEIGHT as i32 + 1) * scale;
+
+        for (char_index, ch) in line.chars().enumerate() {
+            let char_x = x + char_index as i32 * (CHAR_WIDTH as i32 + 1) * scale;
+
+            for (column, bits) in glyph(ch).iter().enumerate() {
+                for row in 0..CHAR_HEIGHT {
+                    if bits & (1 << row) == 0 {
+                        continue;
+                    }
+
+                    // Draw the font pixel as a square
+                    let pixel_x = char_x + column as i32 * scale;
+                    let pixel_y = y + row as i32 * scale;
+                    for py in pixel_y.max(0)..(pixel_y + scale).min(height) {
+                        for px in pixel_x.max(0)..(pixel_x + scale).min(width) {
+                            pixels[px as usize + py as usize * width as usize] = color;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The width in pixels of a line with the amount of characters.
+fn line_width(chars: usize, scale: usize) -> usize {
+    // There's no spacing after the last character
+    (chars * (CHAR_WIDTH + 1)).saturating_sub(1) * scale
+}
+
+/// The columns of a character.
+fn glyph(ch: char) -> &'static [u8; CHAR_WIDTH] {
+    let index = if ch.is_ascii() && !ch.is_ascii_control() {
+        ch as u8 - FIRST_CHAR
+    } else {
+        b'?' - FIRST_CHAR
+    };
+
+    &FONT[index as usize]
+}
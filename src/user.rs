@@ -1,84 +1,37 @@
 use crate::{
     audio::Audio,
-    physics::{Position, Rotation},
+    collision::Collider,
+    input::{Action, InputState},
+    particle::Exhaust,
+    physics::{AngularVelocity, DeltaTime, Position, Rotation, Velocity},
+    projectile::LaserGun,
+    weapon::{Weapon, WeaponKind},
 };
-use specs::{Component, DenseVecStorage};
-use std::f64::consts::PI;
+use specs::{prelude::*, Component, DenseVecStorage};
+use std::f64::consts::FRAC_PI_2;
 
 type Vec2 = vek::Vec2<f64>;
 
-#[const_tweaker::tweak]
-const SPEED: f64 = 0.5;
+#[const_tweaker::tweak(min = 0.0, max = 200.0, step = 1.0)]
+const SPEED: f64 = 30.0;
 #[const_tweaker::tweak(min = 0.0, max = 100.0, step = 1.0)]
 const SPEED_BOOST: f64 = 30.0;
 #[const_tweaker::tweak(min = 0.0, max = 100.0, step = 1.0)]
 const MAX_SPEED: f64 = 50.0;
-#[const_tweaker::tweak(min = 0.0, max = 0.05, step = 0.001)]
-const ROTATION_SPEED: f64 = 0.02;
-#[const_tweaker::tweak(min = 0.0, max = 0.05, step = 0.001)]
-const ROTATION_DIRECTION: f64 = PI / 2.0;
-
-#[derive(Debug, Default)]
-pub struct InputState {
-    up: bool,
-    down: bool,
-    left: bool,
-    right: bool,
-}
-
-impl InputState {
-    /// Instantiate a new keyboard state with nothing pressed.
-    pub fn new() -> Self {
-        Self {
-            up: false,
-            down: false,
-            left: false,
-            right: false,
-        }
-    }
-
-    /// Set the up key as pressed.
-    pub fn set_up_state(&mut self, pressed: bool) {
-        self.up = pressed;
-    }
-
-    /// Get whether the up key is pressed or not.
-    pub fn up_pressed(&self) -> bool {
-        self.up
-    }
-
-    /// Set the down key as pressed.
-    pub fn set_down_state(&mut self, pressed: bool) {
-        self.down = pressed;
-    }
-
-    /// Get whether the down key is pressed or not.
-    pub fn down_pressed(&self) -> bool {
-        self.down
-    }
-
-    /// Set the left key as pressed.
-    pub fn set_left_state(&mut self, pressed: bool) {
-        self.left = pressed;
-    }
-
-    /// Get whether the left key is pressed or not.
-    pub fn left_pressed(&self) -> bool {
-        self.left
-    }
+#[const_tweaker::tweak(min = 0.0, max = 20.0, step = 0.1)]
+const ROTATION_SPEED: f64 = 5.0;
 
-    /// Set the right key as pressed.
-    pub fn set_right_state(&mut self, pressed: bool) {
-        self.right = pressed;
-    }
+/// The fastest the player can go without boosting.
+pub fn max_speed() -> f64 {
+    *MAX_SPEED
+}
 
-    /// Get whether the right key is pressed or not.
-    pub fn right_pressed(&self) -> bool {
-        self.right
-    }
+/// The fastest the player can go while boosting.
+pub fn max_boost_speed() -> f64 {
+    *MAX_SPEED + *SPEED_BOOST
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Camera {
     /// Absolute position.
     pos: Vec2,
@@ -90,8 +43,10 @@ pub struct Camera {
     rot_sin: f64,
     /// Calculated cos that only needs to be calculated once.
     rot_cos: f64,
-    /// Speed.
-    speed: f64,
+    /// Position at the previous physics step.
+    prev_pos: Vec2,
+    /// Rotation at the previous physics step.
+    prev_rot: f64,
 }
 
 impl Camera {
@@ -119,43 +74,59 @@ impl Camera {
         new + self.pivot
     }
 
+    /// Map relative camera coordinates back to normal coordinates, the inverse of `map_pos`.
+    pub fn unmap_pos(&self, pos: Vec2) -> Vec2 {
+        let delta = pos - self.pivot;
+
+        // Rotate in the opposite direction
+        let new = Vec2::new(
+            delta.x * self.rot_cos + delta.y * self.rot_sin,
+            -delta.x * self.rot_sin + delta.y * self.rot_cos,
+        );
+
+        new + self.pivot + self.pos
+    }
+
     /// Map normal rotation with camera rotation.
     pub fn map_rot(&self, rot: &Rotation) -> f64 {
         rot.0 + self.rot
     }
 
-    /// Update the position according to the velocity and speed.
-    pub fn update(&mut self, dt: f64) {
-        self.pos += Vec2::new(
-            (self.rot + *ROTATION_DIRECTION).sin() * self.speed * dt,
-            (self.rot + *ROTATION_DIRECTION).cos() * self.speed * dt,
-        );
+    /// Get the camera between the previous and the current physics step.
+    pub fn interpolate(&self, alpha: f64) -> Self {
+        let mut camera = Self {
+            pos: Vec2::lerp(self.prev_pos, self.pos, alpha),
+            rot: self.prev_rot,
+            ..self.clone()
+        };
+        camera.rotate((self.rot - self.prev_rot) * alpha);
+
+        camera
     }
 
-    /// Hande keyboard and mouse input.
-    pub fn handle_input(&mut self, input: &InputState, audio: &mut Audio) {
-        if input.up_pressed() {
-            // W
-            // A bit of boost
-            self.speed = (self.speed + *SPEED).min(*MAX_SPEED + *SPEED_BOOST);
-
-            audio.play_boost(self.speed);
-        } else {
-            // Remove the boost when the button isn't pressed down
-            self.speed = self.speed.min(*MAX_SPEED);
-        }
-        if input.down_pressed() {
-            // S
-            self.speed = (self.speed - *SPEED).max(0.0);
-        }
-        if input.left_pressed() {
-            // A
-            self.rotate(*ROTATION_SPEED);
-        }
-        if input.right_pressed() {
-            // D
-            self.rotate(-*ROTATION_SPEED);
-        }
+    /// Remember the current position and rotation so the rendering can be interpolated.
+    pub fn store_previous(&mut self) {
+        self.prev_pos = self.pos;
+        self.prev_rot = self.rot;
+    }
+
+    /// The point in the world at the center of the screen.
+    pub fn center(&self) -> Vec2 {
+        self.pos + self.pivot
+    }
+
+    /// Move the previous position along with an object that jumped, like when it wrapped around
+    /// the world, so the interpolation doesn't sweep across everything in between.
+    pub fn translate_previous(&mut self, offset: Vec2) {
+        self.prev_pos += offset;
+    }
+
+    /// Center the camera on a position and rotate it so the heading points up.
+    pub fn look_at(&mut self, center: Vec2, heading: f64) {
+        self.pos = center - self.pivot;
+
+        self.rot = 0.0;
+        self.rotate(-FRAC_PI_2 - heading);
     }
 
     /// Rotate the camera.
@@ -173,3 +144,140 @@ pub struct MovesWithCamera;
 
 #[derive(Component, Debug, Default)]
 pub struct RotatesWithCamera;
+
+/// The rocket controlled by the user.
+#[derive(Component, Debug, Default)]
+pub struct Player;
+
+/// Apply thrust and rotation to the player rocket and pull the trigger depending on the input, the
+/// thrust and rotation scale with how far the actions are pushed.
+pub struct PlayerControlSystem;
+impl<'a> System<'a> for PlayerControlSystem {
+    type SystemData = (
+        Read<'a, DeltaTime>,
+        Read<'a, InputState>,
+        Read<'a, Camera>,
+        Write<'a, Audio>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Rotation>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, AngularVelocity>,
+        WriteStorage<'a, LaserGun>,
+        WriteStorage<'a, Weapon>,
+        WriteStorage<'a, Exhaust>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            dt,
+            input,
+            camera,
+            mut audio,
+            player,
+            rot,
+            mut vel,
+            mut ang_vel,
+            mut gun,
+            mut weapon,
+            mut exhaust,
+        ): Self::SystemData,
+    ) {
+        let dt = dt.to_seconds();
+
+        for (_, weapon) in (&player, &mut weapon).join() {
+            // Switch to the weapon of the first selection held
+            let selected = Action::ALL
+                .iter()
+                .filter(|action| input.pressed(**action))
+                .find_map(|action| WeaponKind::from_action(*action));
+            if let Some(kind) = selected {
+                if kind != weapon.kind {
+                    *weapon = Weapon::new(kind);
+                }
+            }
+        }
+
+        for (_, gun) in (&player, &mut gun).join() {
+            gun.trigger = input.pressed(Action::Fire);
+            gun.autofire = input.autofire_enabled();
+
+            // Aim at the point in the world below the mouse cursor
+            gun.aim = if input.aim_enabled() {
+                let (x, y) = input.cursor();
+                Some(camera.unmap_pos(Vec2::new(x, y)))
+            } else {
+                None
+            };
+        }
+
+        for (_, rot, vel, ang_vel, exhaust) in (
+            &player,
+            &rot,
+            &mut vel,
+            &mut ang_vel,
+            (&mut exhaust).maybe(),
+        )
+            .join()
+        {
+            let heading = Vec2::new(rot.cos(), rot.sin());
+
+            let thrust = input.value(Action::Thrust);
+            if let Some(exhaust) = exhaust {
+                exhaust.thrust = thrust;
+            }
+            if thrust > 0.0 {
+                // A bit of boost
+                vel.0 += heading * *SPEED * thrust * dt;
+                limit_speed(vel, max_boost_speed());
+
+                audio.play_boost(vel.magnitude());
+            } else {
+                // Remove the boost when the button isn't pressed down
+                limit_speed(vel, max_speed());
+            }
+            let brake = input.value(Action::Brake);
+            if brake > 0.0 {
+                let speed = vel.magnitude();
+                if speed > 0.0 {
+                    vel.0 *= (speed - *SPEED * brake * dt).max(0.0) / speed;
+                }
+            }
+
+            // Both directions can be pushed at the same time, they cancel each other out
+            let rotation = input.value(Action::RotateRight) - input.value(Action::RotateLeft);
+            ang_vel.0 += *ROTATION_SPEED * rotation * dt;
+        }
+    }
+}
+
+/// Clamp the length of the velocity.
+fn limit_speed(vel: &mut Velocity, max: f64) {
+    let speed = vel.magnitude();
+    if speed > max {
+        vel.0 *= max / speed;
+    }
+}
+
+/// Let the camera follow the center of the player rocket.
+pub struct CameraFollowSystem;
+impl<'a> System<'a> for CameraFollowSystem {
+    type SystemData = (
+        Write<'a, Camera>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Collider>,
+    );
+
+    fn run(&mut self, (mut camera, player, pos, rot, collider): Self::SystemData) {
+        for (_, pos, rot, collider) in (&player, &pos, &rot, collider.maybe()).join() {
+            let center = match collider {
+                Some(collider) => pos.0 + collider.offset,
+                None => pos.0,
+            };
+
+            camera.look_at(center, rot.0);
+        }
+    }
+}
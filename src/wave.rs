@@ -0,0 +1,137 @@
+use crate::{asteroid::Asteroid, rocket::Rocket, user::Player};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::{fs, path::Path};
+
+/// Asteroids added to every wave after the last defined one.
+const EXTRA_ASTEROIDS: usize = 4;
+/// Enemy rockets added to every wave after the last defined one.
+const EXTRA_ROCKETS: usize = 4;
+/// Asteroid speed added to every wave after the last defined one.
+const EXTRA_ASTEROID_SPEED: f64 = 0.25;
+
+/// The enemies spawned at the start of a wave.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Wave {
+    /// Amount of large asteroids, the streamed asteroid field ignores this.
+    pub asteroids: usize,
+    /// Amount of small enemy rockets.
+    pub rockets: usize,
+    /// Multiplier for the speed of the asteroids.
+    #[serde(default = "default_asteroid_speed")]
+    pub asteroid_speed: f64,
+    /// The chance between 0 and 1 that a destroyed asteroid or enemy drops a power-up.
+    #[serde(default = "default_powerup_chance")]
+    pub powerup_chance: f64,
+}
+
+fn default_asteroid_speed() -> f64 {
+    1.0
+}
+
+fn default_powerup_chance() -> f64 {
+    0.1
+}
+
+/// All waves of a game, loaded from a TOML file:
+///
+/// ```toml
+/// [[wave]]
+/// asteroids = 4
+/// rockets = 0
+///
+/// [[wave]]
+/// asteroids = 8
+/// rockets = 2
+/// asteroid_speed = 1.5
+/// powerup_chance = 0.2
+/// ```
+///
+/// After the last wave is cleared it's repeated with more and faster enemies every time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Waves {
+    #[serde(rename = "wave")]
+    pub waves: Vec<Wave>,
+}
+
+impl Default for Waves {
+    fn default() -> Self {
+        let wave = |asteroids, rockets, asteroid_speed| Wave {
+            asteroids,
+            rockets,
+            asteroid_speed,
+            powerup_chance: default_powerup_chance(),
+        };
+
+        Self {
+            waves: vec![
+                wave(8, 4, 1.0),
+                wave(12, 8, 1.25),
+                wave(16, 12, 1.5),
+                wave(20, 16, 1.75),
+                wave(24, 20, 2.0),
+            ],
+        }
+    }
+}
+
+impl Waves {
+    /// Read the waves from a TOML file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let waves: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        if waves.waves.is_empty() {
+            bail!("\"{}\" doesn't contain any waves", path.display());
+        }
+
+        Ok(waves)
+    }
+
+    /// The wave with the number, the first wave is 1.
+    pub fn get(&self, number: usize) -> Wave {
+        let index = number.max(1) - 1;
+        if let Some(wave) = self.waves.get(index) {
+            return wave.clone();
+        }
+
+        // Keep making the last wave harder
+        let last = self.waves.last().cloned().unwrap_or(Wave {
+            asteroids: 0,
+            rockets: 0,
+            asteroid_speed: default_asteroid_speed(),
+            powerup_chance: default_powerup_chance(),
+        });
+        let extra = index + 1 - self.waves.len();
+
+        Wave {
+            asteroids: last.asteroids + extra * EXTRA_ASTEROIDS,
+            rockets: last.rockets + extra * EXTRA_ROCKETS,
+            asteroid_speed: last.asteroid_speed + extra as f64 * EXTRA_ASTEROID_SPEED,
+            powerup_chance: last.powerup_chance,
+        }
+    }
+}
+
+/// The number of the wave being played, the first wave is 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrentWave(pub usize);
+
+impl Default for CurrentWave {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+/// Whether all enemies of the wave are destroyed.
+///
+/// The streamed asteroid field never runs out of asteroids, so only the rockets count there.
+pub fn cleared(world: &World, streamed: bool) -> bool {
+    let asteroid = world.read_storage::<Asteroid>();
+    let rocket = world.read_storage::<Rocket>();
+    let player = world.read_storage::<Player>();
+
+    let rockets_left = (&rocket, !&player).join().next().is_some();
+    let asteroids_left = !streamed && asteroid.join().next().is_some();
+
+    !rockets_left && !asteroids_left
+}
@@ -0,0 +1,360 @@
+use crate::{
+    audio::Audio,
+    collision::{self, Collider},
+    input::Action,
+    particle::{self, Exhaust, Particle},
+    physics::{
+        DeltaTime, Position, PreviousPosition, Rotation, RotationFollowsVelocity, Velocity,
+        WorldBounds,
+    },
+    powerup::PowerUp,
+    projectile::{self, Laser, LaserGun, Lifetime, Projectile, ProjectileKind},
+    random::WorldRng,
+};
+use specs::{prelude::*, Component, DenseVecStorage};
+
+type Vec2 = vek::Vec2<f64>;
+
+#[const_tweaker::tweak(min = 0.0, max = 2000.0, step = 1.0)]
+const BULLET_SPEED: f64 = 600.0;
+#[const_tweaker::tweak(min = 0.0, max = 5.0, step = 0.01)]
+const BULLET_LIFETIME: f64 = 0.8;
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const BULLET_DAMAGE: f64 = 0.5;
+#[const_tweaker::tweak(min = 0.0, max = 1.0, step = 0.01)]
+const BULLET_FIRE_DELAY: f64 = 0.08;
+
+#[const_tweaker::tweak(min = 0.0, max = 1000.0, step = 1.0)]
+const MISSILE_SPEED: f64 = 250.0;
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const MISSILE_LIFETIME: f64 = 3.0;
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const MISSILE_DAMAGE: f64 = 3.0;
+#[const_tweaker::tweak(min = 0.0, max = 5.0, step = 0.01)]
+const MISSILE_FIRE_DELAY: f64 = 0.6;
+
+/// Seconds the trigger must be held to fully charge the beam.
+#[const_tweaker::tweak(min = 0.0, max = 5.0, step = 0.01)]
+const BEAM_CHARGE_TIME: f64 = 1.5;
+/// The length of a beam that's barely charged.
+#[const_tweaker::tweak(min = 0.0, max = 2000.0, step = 1.0)]
+const BEAM_MIN_STRENGTH: f64 = 300.0;
+/// The length of a fully charged beam.
+#[const_tweaker::tweak(min = 0.0, max = 3000.0, step = 1.0)]
+const BEAM_MAX_STRENGTH: f64 = 1200.0;
+#[const_tweaker::tweak(min = 0.0, max = 10.0, step = 0.1)]
+const BEAM_MIN_DAMAGE: f64 = 1.0;
+#[const_tweaker::tweak(min = 0.0, max = 20.0, step = 0.1)]
+const BEAM_MAX_DAMAGE: f64 = 8.0;
+/// Seconds after firing the beam before it can charge again.
+#[const_tweaker::tweak(min = 0.0, max = 5.0, step = 0.01)]
+const BEAM_COOLDOWN: f64 = 0.5;
+
+#[const_tweaker::tweak(min = 0.0, max = 60.0, step = 0.5)]
+const MINE_LIFETIME: f64 = 20.0;
+#[const_tweaker::tweak(min = 0.0, max = 20.0, step = 0.1)]
+const MINE_DAMAGE: f64 = 5.0;
+#[const_tweaker::tweak(min = 0.0, max = 5.0, step = 0.01)]
+const MINE_FIRE_DELAY: f64 = 1.0;
+
+// The bytes of the pixels are shown as red, green & blue, so the colors are written as 0xBBGGRR
+const BULLET_COLOR: u32 = 0x88_FF_FF;
+const MISSILE_COLOR: u32 = 0xDD_DD_DD;
+const MINE_COLOR: u32 = 0x00_44_FF;
+
+/// The kinds of weapons the player can switch between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeaponKind {
+    /// An instant beam that hits the first object in front of it.
+    Laser,
+    /// Fast small projectiles with little damage.
+    Bullets,
+    /// Slow projectiles that home in on asteroids and enemies.
+    Missiles,
+    /// A laser that's charged while fire is held and shot when it's released.
+    Beam,
+    /// Dropped behind the rocket and explode when anything comes close.
+    Mines,
+}
+
+impl WeaponKind {
+    /// All kinds of weapons, in the order of their keys.
+    pub const ALL: [WeaponKind; 5] = [
+        WeaponKind::Laser,
+        WeaponKind::Bullets,
+        WeaponKind::Missiles,
+        WeaponKind::Beam,
+        WeaponKind::Mines,
+    ];
+
+    /// The name shown on the HUD.
+    pub fn name(self) -> &'static str {
+        match self {
+            WeaponKind::Laser => "LASER",
+            WeaponKind::Bullets => "BULLETS",
+            WeaponKind::Missiles => "MISSILES",
+            WeaponKind::Beam => "BEAM",
+            WeaponKind::Mines => "MINES",
+        }
+    }
+
+    /// The weapon selected by an action.
+    pub fn from_action(action: Action) -> Option<Self> {
+        match action {
+            Action::SelectLaser => Some(WeaponKind::Laser),
+            Action::SelectBullets => Some(WeaponKind::Bullets),
+            Action::SelectMissiles => Some(WeaponKind::Missiles),
+            Action::SelectBeam => Some(WeaponKind::Beam),
+            Action::SelectMines => Some(WeaponKind::Mines),
+            _ => None,
+        }
+    }
+
+    /// Seconds between shots of the weapons that fire projectiles.
+    fn fire_delay(self) -> f64 {
+        match self {
+            WeaponKind::Bullets => *BULLET_FIRE_DELAY,
+            WeaponKind::Missiles => *MISSILE_FIRE_DELAY,
+            WeaponKind::Mines => *MINE_FIRE_DELAY,
+            WeaponKind::Laser | WeaponKind::Beam => 0.0,
+        }
+    }
+}
+
+/// The weapon selected on a gun, without it the gun always shoots lasers.
+#[derive(Component, Debug)]
+pub struct Weapon {
+    pub kind: WeaponKind,
+    /// How far the beam is charged, between 0 and 1.
+    pub charge: f64,
+}
+
+impl Weapon {
+    /// Instantiate a new weapon of the kind that's not charged.
+    pub fn new(kind: WeaponKind) -> Self {
+        Self { kind, charge: 0.0 }
+    }
+}
+
+/// Fire the weapons that aren't plain lasers, those are fired by the laser gun system.
+///
+/// The trigger, aim and power-up modifiers of the laser gun are used for every weapon.
+pub struct WeaponSystem;
+impl<'a> System<'a> for WeaponSystem {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, DeltaTime>,
+        Read<'a, WorldBounds>,
+        Read<'a, LazyUpdate>,
+        Write<'a, Audio>,
+        Write<'a, WorldRng>,
+        WriteStorage<'a, Weapon>,
+        WriteStorage<'a, LaserGun>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Rotation>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, PowerUp>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            dt,
+            bounds,
+            updater,
+            mut audio,
+            mut rng,
+            mut weapon,
+            mut gun,
+            pos,
+            vel,
+            rot,
+            collider,
+            power_up,
+        ): Self::SystemData,
+    ) {
+        let dt = dt.to_seconds();
+
+        for (entity, weapon, gun, gun_pos, vel, rot) in
+            (&entities, &mut weapon, &mut gun, &pos, &vel, &rot).join()
+        {
+            // Shoot from the center of the rocket
+            let center = match collider.get(entity) {
+                Some(collider) => gun_pos.0 + collider.offset,
+                None => gun_pos.0,
+            };
+            let heading = Vec2::new(rot.cos(), rot.sin());
+            let aim = match gun.aim {
+                Some(target) if target != center => (target - center).normalized(),
+                _ => heading,
+            };
+
+            match weapon.kind {
+                WeaponKind::Laser => {
+                    weapon.charge = 0.0;
+                }
+                WeaponKind::Beam => {
+                    gun.cooldown = (gun.cooldown - dt).max(0.0);
+
+                    // Charge while the trigger is held and fire when it's released
+                    let triggered = gun.trigger;
+                    gun.was_triggered = triggered;
+                    if triggered {
+                        if gun.cooldown <= 0.0 {
+                            weapon.charge = (weapon.charge + dt / *BEAM_CHARGE_TIME).min(1.0);
+                        }
+                        continue;
+                    }
+                    if weapon.charge <= 0.0 {
+                        continue;
+                    }
+
+                    let charge = weapon.charge;
+                    weapon.charge = 0.0;
+                    gun.cooldown = *BEAM_COOLDOWN * gun.modifier.cooldown;
+
+                    let strength = (*BEAM_MIN_STRENGTH
+                        + (*BEAM_MAX_STRENGTH - *BEAM_MIN_STRENGTH) * charge)
+                        * gun.modifier.strength;
+                    let damage = *BEAM_MIN_DAMAGE + (*BEAM_MAX_DAMAGE - *BEAM_MIN_DAMAGE) * charge;
+                    let nose = center + aim * gun.nose;
+
+                    // Find the first object in the path of the beam
+                    let hit = collision::raycast(
+                        (&entities, &pos, &collider, !&power_up)
+                            .join()
+                            .map(|(entity, pos, collider, _)| (entity, pos, collider)),
+                        &bounds,
+                        nose,
+                        aim,
+                        strength,
+                        entity,
+                    );
+
+                    if let Some(hit) = hit {
+                        particle::sparks(&entities, &updater, &mut *rng, hit.point, aim);
+                    }
+
+                    projectile::shoot_laser(
+                        &entities,
+                        &updater,
+                        Laser::new(entity, damage, hit),
+                        &nose,
+                        aim.y.atan2(aim.x),
+                        strength,
+                    );
+
+                    audio.play_beam(charge);
+                }
+                WeaponKind::Mines => {
+                    if !gun.pull(dt, weapon.kind.fire_delay()) {
+                        continue;
+                    }
+
+                    // Drop it behind the rocket so it's not set off by the rocket itself
+                    let pos = center - heading * gun.nose;
+                    spawn_projectile(
+                        &entities,
+                        &updater,
+                        Projectile {
+                            owner: entity,
+                            damage: *MINE_DAMAGE,
+                            kind: ProjectileKind::Mine,
+                        },
+                        pos,
+                        Vec2::zero(),
+                        *MINE_LIFETIME,
+                    );
+
+                    audio.play_mine();
+                }
+                WeaponKind::Bullets | WeaponKind::Missiles => {
+                    if !gun.pull(dt, weapon.kind.fire_delay()) {
+                        continue;
+                    }
+
+                    // Fan the projectiles out around the aim
+                    let count = gun.modifier.count.max(1);
+                    for index in 0..count {
+                        let offset =
+                            (index as f64 - (count - 1) as f64 / 2.0) * gun.modifier.spread;
+                        let (sin, cos) = offset.sin_cos();
+                        let dir = Vec2::new(aim.x * cos - aim.y * sin, aim.x * sin + aim.y * cos);
+                        let nose = center + dir * gun.nose;
+
+                        if weapon.kind == WeaponKind::Bullets {
+                            spawn_projectile(
+                                &entities,
+                                &updater,
+                                Projectile {
+                                    owner: entity,
+                                    damage: *BULLET_DAMAGE * gun.modifier.strength,
+                                    kind: ProjectileKind::Bullet,
+                                },
+                                nose,
+                                vel.0 + dir * *BULLET_SPEED,
+                                *BULLET_LIFETIME,
+                            );
+                        } else {
+                            let missile = spawn_projectile(
+                                &entities,
+                                &updater,
+                                Projectile {
+                                    owner: entity,
+                                    damage: *MISSILE_DAMAGE * gun.modifier.strength,
+                                    kind: ProjectileKind::Missile,
+                                },
+                                nose,
+                                dir * *MISSILE_SPEED,
+                                *MISSILE_LIFETIME,
+                            );
+
+                            // Leave a trail behind the missile
+                            updater.insert(missile, Rotation(dir.y.atan2(dir.x)));
+                            updater.insert(missile, RotationFollowsVelocity);
+                            updater.insert(missile, Exhaust::new(0.3));
+                        }
+                    }
+
+                    if weapon.kind == WeaponKind::Bullets {
+                        audio.play_bullet();
+                    } else {
+                        audio.play_missile();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Spawn a projectile drawn as a particle that disappears at the end of its lifetime.
+fn spawn_projectile(
+    entities: &Entities,
+    updater: &LazyUpdate,
+    projectile: Projectile,
+    pos: Vec2,
+    vel: Vec2,
+    lifetime: f64,
+) -> Entity {
+    let (color, size) = match projectile.kind {
+        ProjectileKind::Bullet => (BULLET_COLOR, 2),
+        ProjectileKind::Missile => (MISSILE_COLOR, 3),
+        ProjectileKind::Mine => (MINE_COLOR, 4),
+    };
+
+    updater
+        .create_entity(entities)
+        .with(projectile)
+        .with(Particle {
+            color,
+            size,
+            lifetime,
+        })
+        .with(Position(pos))
+        .with(PreviousPosition(pos))
+        .with(Velocity(vel))
+        .with(Lifetime(lifetime))
+        .build()
+}